@@ -1,11 +1,16 @@
-use crate::DEFAULT_INPUT_PATH;
-use crate::DEFAULT_OUTPUT_PATH;
+use crate::editor::DEFAULT_REVEAL_TIMEOUT;
+use crate::key::{DEFAULT_ESCAPE_TIMEOUT, Key};
+use crate::keymap::Keymap;
+use crate::renderer::FeedbackRenderer;
+use crate::theme::Theme;
 use std::io::{Cursor, Read, Write};
+use std::sync::Arc;
+use std::time::Duration;
 
 /// Controls visual feedback when the user types a password.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 #[non_exhaustive]
-pub(crate) enum PasswordFeedback {
+pub enum PasswordFeedback {
     /// Show nothing while typing (current default behavior).
     #[default]
     Hide,
@@ -16,10 +21,130 @@ pub(crate) enum PasswordFeedback {
     /// mask char for the rest.
     /// e.g. `PartialMask('*', 3)` shows first 3 chars in plaintext, then stars.
     PartialMask(char, usize),
+    /// Show each character in plaintext as it is typed, then the given mask char once the
+    /// duration has passed or the next key is pressed.
+    /// e.g. `FlashLast('*', Duration::from_secs(1))` works like password fields on phones.
+    FlashLast(char, Duration),
+    /// Show between 1 and 3 of the given mask char for every character typed, picked at random,
+    /// so that the length of the password cannot be told from the screen.
+    RandomMask(char),
+    /// Show a small animation made of the given char that moves on every key, so that the user
+    /// can tell keys are received without the screen telling anything about the password.
+    Indicator(char),
+    /// Show how many characters were typed, e.g. `(12 characters)`, rewritten after every key.
+    /// With a maximum, the limit is added once the password gets close to it,
+    /// e.g. `Counter(Some(64))` shows `(60 characters, max 64)`.
+    Counter(Option<usize>),
+}
+
+/// Controls what happens when text is pasted in a terminal supporting bracketed paste mode.
+///
+/// The pasted text is inserted at once, as opposed to typed key by key, so a newline at the end of
+/// it does not submit the password.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum PastePolicy {
+    /// Insert the pasted text as is, newlines and other control characters included.
+    Allow,
+    /// Refuse pasted text, making the read fail with the given message.
+    Reject(String),
+    /// Insert the pasted text without the newlines at its end, as password managers often add
+    /// one (default behavior). Anything else, trailing spaces and tabs included, is kept.
+    #[default]
+    StripTrailingNewlines,
+    /// Insert the pasted text without any control character, e.g. tabs, newlines or the escape
+    /// sequences of text copied from a terminal.
+    StripControlCharacters,
+}
+
+/// Controls what is left on screen once the password is submitted or typing is cancelled, e.g.
+/// so that the characters shown by `PasswordFeedback::PartialMask` do not stay in the scrollback.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum AfterSubmit {
+    /// Leave the prompt and the feedback as they are and move to the next line (default behavior).
+    #[default]
+    Keep,
+    /// Erase the line of the prompt, leaving the cursor at its start.
+    Erase,
+    /// Redraw the line with the prompt followed by the given text instead of the feedback,
+    /// e.g. `Password: [accepted]`, then move to the next line.
+    Replace {
+        submitted: String,
+        cancelled: String,
+    },
+}
+
+/// Controls what happens to the keys typed before the prompt shows up, which would otherwise be
+/// read as the start of the password.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum Typeahead {
+    /// Discard them, so that text pushed into the terminal by another program, e.g. with
+    /// `TIOCSTI`, does not end up in the password either.
+    Flush,
+    /// Read them as part of the password (default behavior).
+    #[default]
+    Keep,
+    /// Discard them and tell the user, above the prompt.
+    Warn,
+}
+
+/// Controls what happens when no terminal is found to read the password from, e.g. in containers
+/// started without `-t`, in programs started with `setsid` or in some CI runners.
+///
+/// The controlling terminal is tried first, then the terminal stdin, stderr or stdout is
+/// connected to, in that order. On Windows, `CONIN$` and `CONOUT$` are tried first, then the
+/// console of stdin for input, and of stderr or stdout for output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum TerminalFallback {
+    /// Fail with an error telling what was tried (default behavior).
+    #[default]
+    Fail,
+    /// Read the password as a line from stdin, e.g. piped by a script, and write the prompt to
+    /// stderr.
+    Stdin,
+}
+
+/// Controls what happens when typing is cancelled, e.g. with Ctrl-C.
+///
+/// Reading the password fails with an [`std::io::ErrorKind::Interrupted`] error wrapping
+/// [`crate::Cancelled`] in every case, the terminal being restored once the read returns.
+#[derive(Clone, Default)]
+#[non_exhaustive]
+pub enum OnCancel {
+    /// Raise `SIGINT` (a Ctrl-C event on Windows), as the terminal would have, which ends programs
+    /// that do not handle it (default behavior).
+    #[default]
+    RaiseSigint,
+    /// Only return the error, e.g. for programs that treat Ctrl-C as cancelling the prompt.
+    ReturnError,
+    /// Call the given function, then return the error.
+    Callback(Arc<dyn Fn() + Send + Sync>),
+}
+
+impl OnCancel {
+    /// Calls `callback` when typing is cancelled.
+    pub fn callback(callback: impl Fn() + Send + Sync + 'static) -> OnCancel {
+        OnCancel::Callback(Arc::new(callback))
+    }
+}
+
+impl std::fmt::Debug for OnCancel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OnCancel::RaiseSigint => f.write_str("RaiseSigint"),
+            OnCancel::ReturnError => f.write_str("ReturnError"),
+            OnCancel::Callback(_) => f.write_str("Callback(..)"),
+        }
+    }
 }
 
 /// Specifies the source for input.
 pub(crate) enum InputTarget {
+    /// The terminal the user types in, see [`TerminalFallback`]
+    Terminal,
     FilePath(String),
     Reader(Box<dyn std::io::Read>),
 }
@@ -29,18 +154,64 @@ pub(crate) enum InputTarget {
 /// This enum defines where input is read from or where output is written to.
 /// It supports file paths, in-memory cursors, or no input/output at all.
 pub(crate) enum OutputTarget {
+    /// The terminal the user types in, see [`TerminalFallback`]
+    Terminal,
     FilePath(String),
     Writer(Box<dyn std::io::Write>),
     Void,
 }
 
+/// Settings for editing the password, as opposed to where it is read from and written to.
+pub(crate) struct ReadOptions {
+    pub(crate) password_feedback: PasswordFeedback,
+    /// Draws the password instead of the built-in renderer for `password_feedback`
+    pub(crate) renderer: Option<Box<dyn FeedbackRenderer>>,
+    pub(crate) keymap: Keymap,
+    pub(crate) escape_timeout: Duration,
+    pub(crate) paste_policy: PastePolicy,
+    pub(crate) after_submit: AfterSubmit,
+    pub(crate) on_cancel: OnCancel,
+    pub(crate) typeahead: Typeahead,
+    pub(crate) theme: Theme,
+    /// Name of the terminal whose capabilities are looked up, `TERM` if not set
+    pub(crate) terminal: Option<String>,
+    pub(crate) reveal_key: Option<Key>,
+    pub(crate) reveal_timeout: Duration,
+    /// Written before reading the password, and again whenever the password is repainted
+    pub(crate) prompt: String,
+}
+
+impl Default for ReadOptions {
+    fn default() -> Self {
+        ReadOptions {
+            password_feedback: PasswordFeedback::default(),
+            renderer: None,
+            keymap: Keymap::default(),
+            escape_timeout: DEFAULT_ESCAPE_TIMEOUT,
+            paste_policy: PastePolicy::default(),
+            after_submit: AfterSubmit::default(),
+            on_cancel: OnCancel::default(),
+            typeahead: Typeahead::default(),
+            theme: Theme::default(),
+            terminal: None,
+            reveal_key: None,
+            reveal_timeout: DEFAULT_REVEAL_TIMEOUT,
+            prompt: String::new(),
+        }
+    }
+}
+
 /// Configuration for prompting and reading a password.
 ///
 /// Obtained from `ConfigBuilder::build()`.
 pub struct Config {
-    pub(crate) password_feedback: PasswordFeedback,
+    pub(crate) options: ReadOptions,
     pub(crate) input: InputTarget,
     pub(crate) output: OutputTarget,
+    /// Whether the terminal is restored from signal handlers and panic hooks too
+    #[cfg(all(target_family = "unix", not(target_family = "wasm")))]
+    pub(crate) restore_on_exit: bool,
+    pub(crate) terminal_fallback: TerminalFallback,
 }
 
 /// A builder for creating a [`Config`].
@@ -61,6 +232,65 @@ pub struct Config {
 ///     .build();
 /// ```
 ///
+/// ## Customising key bindings
+/// ```
+/// use rpassword::{Action, ConfigBuilder, Key, Keymap};
+///
+/// let config = ConfigBuilder::new()
+///     .keymap(Keymap::minimal().bind(Key::Esc, Action::Cancel))
+///     .build();
+/// ```
+///
+/// ## Stripping newlines from pasted passwords, or refusing them
+/// ```
+/// use rpassword::{ConfigBuilder, PastePolicy};
+///
+/// let config = ConfigBuilder::new()
+///     .paste_policy(PastePolicy::StripTrailingNewlines) // this is the default
+///     .paste_policy(PastePolicy::Reject("pasting is not allowed".to_string()))
+///     .build();
+/// ```
+///
+/// ## Leaving no trace of the password on screen
+/// ```
+/// use rpassword::{AfterSubmit, ConfigBuilder};
+///
+/// let config = ConfigBuilder::new()
+///     .password_feedback_partial_mask('*', 3)
+///     .after_submit(AfterSubmit::Erase)
+///     .after_submit(AfterSubmit::Replace {
+///         submitted: "[accepted]".to_string(),
+///         cancelled: "[cancelled]".to_string(),
+///     })
+///     .build();
+/// ```
+///
+/// ## Cancelling the prompt on Ctrl-C instead of ending the program
+/// ```no_run
+/// use rpassword::{Cancelled, ConfigBuilder, OnCancel};
+///
+/// let config = ConfigBuilder::new().on_cancel(OnCancel::ReturnError).build();
+/// match rpassword::prompt_password_with_config("Password: ", config) {
+///     Ok(password) => println!("Got {} chars", password.len()),
+///     Err(err) if err.get_ref().is_some_and(|err| err.is::<Cancelled>()) => {
+///         println!("Cancelled")
+///     }
+///     Err(err) => eprintln!("{err}"),
+/// }
+/// ```
+///
+/// ## Letting the user check the password before submitting it
+/// ```
+/// use std::time::Duration;
+/// use rpassword::ConfigBuilder;
+///
+/// let config = ConfigBuilder::new()
+///     .password_feedback_mask('*')
+///     .allow_reveal() // Ctrl-R shows the password in plaintext
+///     .reveal_timeout(Duration::from_secs(3)) // and it gets masked again 3 seconds later
+///     .build();
+/// ```
+///
 /// ## Setting custom input file path
 /// ```
 /// use rpassword::{ConfigBuilder};
@@ -117,17 +347,23 @@ pub struct Config {
 ///     .build();
 /// ```
 pub struct ConfigBuilder {
-    feedback: PasswordFeedback,
+    options: ReadOptions,
     input: InputTarget,
     output: OutputTarget,
+    #[cfg(all(target_family = "unix", not(target_family = "wasm")))]
+    restore_on_exit: bool,
+    terminal_fallback: TerminalFallback,
 }
 
 impl Default for ConfigBuilder {
     fn default() -> Self {
         ConfigBuilder {
-            feedback: PasswordFeedback::default(),
-            input: InputTarget::FilePath(DEFAULT_INPUT_PATH.to_string()),
-            output: OutputTarget::FilePath(DEFAULT_OUTPUT_PATH.to_string()),
+            options: ReadOptions::default(),
+            input: InputTarget::Terminal,
+            output: OutputTarget::Terminal,
+            #[cfg(all(target_family = "unix", not(target_family = "wasm")))]
+            restore_on_exit: false,
+            terminal_fallback: TerminalFallback::default(),
         }
     }
 }
@@ -140,7 +376,10 @@ impl ConfigBuilder {
     /// Sets the visual feedback to a mask with the given character.
     pub fn password_feedback_mask(self, mask: char) -> ConfigBuilder {
         ConfigBuilder {
-            feedback: PasswordFeedback::Mask(mask),
+            options: ReadOptions {
+                password_feedback: PasswordFeedback::Mask(mask),
+                ..self.options
+            },
             ..self
         }
     }
@@ -148,7 +387,58 @@ impl ConfigBuilder {
     /// Sets the visual feedback to a mask with the given character.
     pub fn password_feedback_partial_mask(self, mask: char, length: usize) -> ConfigBuilder {
         ConfigBuilder {
-            feedback: PasswordFeedback::PartialMask(mask, length),
+            options: ReadOptions {
+                password_feedback: PasswordFeedback::PartialMask(mask, length),
+                ..self.options
+            },
+            ..self
+        }
+    }
+
+    /// Sets the visual feedback to briefly show each typed character before masking it with the
+    /// given character.
+    pub fn password_feedback_flash_last(self, mask: char, timeout: Duration) -> ConfigBuilder {
+        ConfigBuilder {
+            options: ReadOptions {
+                password_feedback: PasswordFeedback::FlashLast(mask, timeout),
+                ..self.options
+            },
+            ..self
+        }
+    }
+
+    /// Sets the visual feedback to a random number of the given mask character per character
+    /// typed, hiding the length of the password.
+    pub fn password_feedback_random_mask(self, mask: char) -> ConfigBuilder {
+        ConfigBuilder {
+            options: ReadOptions {
+                password_feedback: PasswordFeedback::RandomMask(mask),
+                ..self.options
+            },
+            ..self
+        }
+    }
+
+    /// Sets the visual feedback to an animation made of the given character, hiding the length
+    /// of the password.
+    pub fn password_feedback_indicator(self, mask: char) -> ConfigBuilder {
+        ConfigBuilder {
+            options: ReadOptions {
+                password_feedback: PasswordFeedback::Indicator(mask),
+                ..self.options
+            },
+            ..self
+        }
+    }
+
+    /// Sets the visual feedback to the number of characters typed, warning about the given
+    /// maximum length, if any, once the password gets close to it.
+    pub fn password_feedback_counter(self, max: Option<usize>) -> ConfigBuilder {
+        ConfigBuilder {
+            options: ReadOptions {
+                password_feedback: PasswordFeedback::Counter(max),
+                ..self.options
+            },
             ..self
         }
     }
@@ -156,7 +446,173 @@ impl ConfigBuilder {
     /// Sets the visual feedback none, hides the password entirely.
     pub fn password_feedback_hide(self) -> ConfigBuilder {
         ConfigBuilder {
-            feedback: PasswordFeedback::Hide,
+            options: ReadOptions {
+                password_feedback: PasswordFeedback::Hide,
+                ..self.options
+            },
+            ..self
+        }
+    }
+
+    /// Sets a custom visual feedback, replacing the `password_feedback_*` settings, see
+    /// [`FeedbackRenderer`].
+    pub fn feedback_renderer(self, renderer: impl FeedbackRenderer + 'static) -> ConfigBuilder {
+        ConfigBuilder {
+            options: ReadOptions {
+                renderer: Some(Box::new(renderer)),
+                ..self.options
+            },
+            ..self
+        }
+    }
+
+    /// Sets the key bindings used while typing the password, see [`Keymap`].
+    pub fn keymap(self, keymap: Keymap) -> ConfigBuilder {
+        ConfigBuilder {
+            options: ReadOptions {
+                keymap,
+                ..self.options
+            },
+            ..self
+        }
+    }
+
+    /// Sets how long to wait for the rest of an escape sequence, such as an arrow key, after Esc.
+    ///
+    /// If nothing follows within that delay, Esc is considered to be pressed on its own, which can
+    /// be bound to an action with [`ConfigBuilder::keymap`]. Defaults to 50ms.
+    pub fn escape_timeout(self, escape_timeout: Duration) -> ConfigBuilder {
+        ConfigBuilder {
+            options: ReadOptions {
+                escape_timeout,
+                ..self.options
+            },
+            ..self
+        }
+    }
+
+    /// Sets what happens when text is pasted, see [`PastePolicy`].
+    pub fn paste_policy(self, paste_policy: PastePolicy) -> ConfigBuilder {
+        ConfigBuilder {
+            options: ReadOptions {
+                paste_policy,
+                ..self.options
+            },
+            ..self
+        }
+    }
+
+    /// Sets what is left on screen once the password is submitted or typing is cancelled, see
+    /// [`AfterSubmit`].
+    pub fn after_submit(self, after_submit: AfterSubmit) -> ConfigBuilder {
+        ConfigBuilder {
+            options: ReadOptions {
+                after_submit,
+                ..self.options
+            },
+            ..self
+        }
+    }
+
+    /// Sets what happens when typing is cancelled, e.g. with Ctrl-C, see [`OnCancel`].
+    pub fn on_cancel(self, on_cancel: OnCancel) -> ConfigBuilder {
+        ConfigBuilder {
+            options: ReadOptions {
+                on_cancel,
+                ..self.options
+            },
+            ..self
+        }
+    }
+
+    /// Sets what happens to the keys typed before the prompt shows up, see [`Typeahead`].
+    pub fn typeahead(self, typeahead: Typeahead) -> ConfigBuilder {
+        ConfigBuilder {
+            options: ReadOptions {
+                typeahead,
+                ..self.options
+            },
+            ..self
+        }
+    }
+
+    /// Restores the terminal even when the program ends while the password is read without
+    /// unwinding the stack, i.e. on `SIGTERM`, `SIGHUP`, `SIGQUIT` and `SIGINT`, or on a panic
+    /// with `panic = "abort"`. Off by default, as it installs signal handlers and a panic hook.
+    ///
+    /// Handlers installed before are called once the terminal is restored, the terminal being
+    /// configured again if they let the program go on. Signals without a handler are raised again,
+    /// ending the program as they would have. Only on Unix.
+    pub fn restore_terminal_on_exit(self) -> ConfigBuilder {
+        ConfigBuilder {
+            #[cfg(all(target_family = "unix", not(target_family = "wasm")))]
+            restore_on_exit: true,
+            ..self
+        }
+    }
+
+    /// Sets the styles of the prompt and the feedback, along with a hint shown next to the
+    /// prompt, see [`Theme`].
+    pub fn theme(self, theme: Theme) -> ConfigBuilder {
+        ConfigBuilder {
+            options: ReadOptions {
+                theme,
+                ..self.options
+            },
+            ..self
+        }
+    }
+
+    /// Looks up the capabilities of the terminal with the given name in the terminfo database,
+    /// instead of the one named by the `TERM` environment variable. Useful when the output goes to
+    /// another terminal than the one the program runs in.
+    ///
+    /// On terminals that cannot erase what was drawn, the feedback is hidden.
+    pub fn terminal(self, name: impl Into<String>) -> ConfigBuilder {
+        ConfigBuilder {
+            options: ReadOptions {
+                terminal: Some(name.into()),
+                ..self.options
+            },
+            ..self
+        }
+    }
+
+    /// Lets the user show the password in plaintext while typing by pressing Ctrl-R, and mask it
+    /// again by pressing Ctrl-R a second time, see [`ConfigBuilder::reveal_key`].
+    pub fn allow_reveal(self) -> ConfigBuilder {
+        self.reveal_key(Key::Ctrl('r'))
+    }
+
+    /// Lets the user show the password in plaintext while typing by pressing the given key, and
+    /// mask it again by pressing the key a second time. Revealing is off by default.
+    pub fn reveal_key(self, key: Key) -> ConfigBuilder {
+        ConfigBuilder {
+            options: ReadOptions {
+                reveal_key: Some(key),
+                ..self.options
+            },
+            ..self
+        }
+    }
+
+    /// Sets how long the password stays revealed before being masked again, 5 seconds by default.
+    /// Use [`Duration::MAX`] to keep it revealed until the reveal key is pressed again.
+    pub fn reveal_timeout(self, reveal_timeout: Duration) -> ConfigBuilder {
+        ConfigBuilder {
+            options: ReadOptions {
+                reveal_timeout,
+                ..self.options
+            },
+            ..self
+        }
+    }
+
+    /// Sets what happens when no terminal is found, see [`TerminalFallback`]. Only applies to the
+    /// input and output that are not set otherwise.
+    pub fn terminal_fallback(self, terminal_fallback: TerminalFallback) -> ConfigBuilder {
+        ConfigBuilder {
+            terminal_fallback,
             ..self
         }
     }
@@ -212,9 +668,12 @@ impl ConfigBuilder {
     /// Builds the final [`Config`].
     pub fn build(self) -> Config {
         Config {
-            password_feedback: self.feedback,
+            options: self.options,
             input: self.input,
             output: self.output,
+            #[cfg(all(target_family = "unix", not(target_family = "wasm")))]
+            restore_on_exit: self.restore_on_exit,
+            terminal_fallback: self.terminal_fallback,
         }
     }
 }
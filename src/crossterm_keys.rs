@@ -0,0 +1,102 @@
+use crate::key::Key;
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+
+/// Converts a key event from crossterm, so it can be fed to [`crate::PasswordEditor`].
+///
+/// Key releases and keys that the editor has no use for, such as function keys, become
+/// `Key::Unknown` with no bytes, which the editor ignores.
+impl From<KeyEvent> for Key {
+    fn from(event: KeyEvent) -> Key {
+        if event.kind == KeyEventKind::Release {
+            return Key::Unknown(Vec::new());
+        }
+
+        let control = event.modifiers.contains(KeyModifiers::CONTROL);
+        let key = match event.code {
+            KeyCode::Char(c) if control => Key::Ctrl(c.to_ascii_lowercase()),
+            KeyCode::Char(c) => Key::Char(c),
+            KeyCode::Enter => Key::Enter,
+            KeyCode::Tab => Key::Tab,
+            KeyCode::Backspace => Key::Backspace,
+            KeyCode::Delete => Key::Delete,
+            KeyCode::Esc => Key::Esc,
+            KeyCode::Up => Key::Up,
+            KeyCode::Down => Key::Down,
+            KeyCode::Left if control => Key::CtrlLeft,
+            KeyCode::Left => Key::Left,
+            KeyCode::Right if control => Key::CtrlRight,
+            KeyCode::Right => Key::Right,
+            KeyCode::Home => Key::Home,
+            KeyCode::End => Key::End,
+            _ => return Key::Unknown(Vec::new()),
+        };
+
+        if event.modifiers.contains(KeyModifiers::ALT) {
+            Key::Alt(Box::new(key))
+        } else {
+            key
+        }
+    }
+}
+
+/// Converts key and paste events from crossterm, giving back any other event.
+impl TryFrom<Event> for Key {
+    type Error = Event;
+
+    fn try_from(event: Event) -> Result<Key, Event> {
+        match event {
+            Event::Key(event) => Ok(event.into()),
+            Event::Paste(text) => Ok(Key::Paste(text)),
+            event => Err(event),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::key::Key;
+    use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyEventState, KeyModifiers};
+
+    fn press(code: KeyCode, modifiers: KeyModifiers) -> Key {
+        KeyEvent::new(code, modifiers).into()
+    }
+
+    #[test]
+    fn key_from_crossterm_key_event() {
+        assert_eq!(
+            press(KeyCode::Char('A'), KeyModifiers::SHIFT),
+            Key::Char('A')
+        );
+        assert_eq!(
+            press(KeyCode::Char('w'), KeyModifiers::CONTROL),
+            Key::Ctrl('w')
+        );
+        assert_eq!(
+            press(KeyCode::Char('b'), KeyModifiers::ALT),
+            Key::Alt(Box::new(Key::Char('b')))
+        );
+        assert_eq!(press(KeyCode::Left, KeyModifiers::CONTROL), Key::CtrlLeft);
+        assert_eq!(press(KeyCode::Enter, KeyModifiers::NONE), Key::Enter);
+        assert_eq!(
+            press(KeyCode::F(1), KeyModifiers::NONE),
+            Key::Unknown(Vec::new())
+        );
+
+        let release = KeyEvent {
+            code: KeyCode::Char('a'),
+            modifiers: KeyModifiers::NONE,
+            kind: KeyEventKind::Release,
+            state: KeyEventState::NONE,
+        };
+        assert_eq!(Key::from(release), Key::Unknown(Vec::new()));
+    }
+
+    #[test]
+    fn key_from_crossterm_event() {
+        assert_eq!(
+            Key::try_from(Event::Paste("secret".to_string())),
+            Ok(Key::Paste("secret".to_string()))
+        );
+        assert_eq!(Key::try_from(Event::FocusLost), Err(Event::FocusLost));
+    }
+}
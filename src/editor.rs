@@ -0,0 +1,567 @@
+use crate::config::{AfterSubmit, PasswordFeedback, PastePolicy};
+use crate::feedback::FeedbackState;
+use crate::key::Key;
+use crate::keymap::{Action, Keymap};
+use crate::renderer::{FeedbackRenderer, renderer, renderer_with_mask};
+use crate::terminfo::Capabilities;
+use crate::theme::Theme;
+use rtoolbox::safe_string::SafeString;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// How long the password stays revealed when no other delay is set
+pub(crate) const DEFAULT_REVEAL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Tells the current time, so that tests can control timeouts
+pub(crate) type Clock = Arc<dyn Fn() -> Instant + Send + Sync>;
+
+/// Whether the user is still typing the password after a key was handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EditStatus {
+    /// The password is still being typed
+    Editing,
+    /// The user accepted the password, e.g. with Enter
+    Submitted,
+    /// The user aborted, e.g. with Ctrl-C
+    Cancelled,
+    /// The user ended the input on an empty password, e.g. with Ctrl-D
+    EndOfFile,
+    /// The user pasted text while the [`PastePolicy`] rejects it
+    PasteRejected,
+    /// The user asked to suspend the program, e.g. with Ctrl-Z. Editing goes on once the program
+    /// is resumed and the caller draws the [`PasswordEditor::resume`] output.
+    Suspended,
+}
+
+/// What the editor did with a key: the bytes to draw and whether editing is over.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Edit {
+    /// Bytes to write to the terminal to keep the feedback in sync, may be empty
+    pub output: String,
+    pub status: EditStatus,
+}
+
+/// Edits a password one key at a time, without doing any I/O.
+///
+/// This is what [`crate::read_password_with_config`] uses under the hood. TUI, GUI or embedded
+/// applications can feed it keys from their own event loop and get the same masking and editing
+/// behaviour, either by writing the returned [`Edit::output`] to a terminal, or by drawing
+/// [`PasswordEditor::display`] themselves.
+///
+/// # Examples
+/// ```
+/// use rpassword::{EditStatus, Key, PasswordEditor, PasswordFeedback};
+///
+/// let mut editor = PasswordEditor::new(PasswordFeedback::Mask('*'));
+/// for key in [Key::Char('a'), Key::Char('c'), Key::Left, Key::Char('b')] {
+///     editor.handle_key(key);
+/// }
+/// assert_eq!(editor.display(), "***");
+/// assert_eq!(editor.cursor(), 2);
+///
+/// let edit = editor.handle_key(Key::Enter);
+/// assert_eq!(edit.status, EditStatus::Submitted);
+/// assert_eq!(editor.into_password(), "abc");
+/// ```
+pub struct PasswordEditor {
+    state: FeedbackState,
+    /// Built-in feedback drawn by the renderer, if any, so that a theme can change its mask
+    feedback: Option<PasswordFeedback>,
+    keymap: Keymap,
+    paste_policy: PastePolicy,
+    reveal_timeout: Duration,
+    clock: Clock,
+    /// When the password revealed with [`Action::Reveal`] gets masked again
+    conceal_at: Option<Instant>,
+    /// When the renderer gets told its timeout passed, e.g. to mask the char shown with
+    /// [`PasswordFeedback::FlashLast`]
+    renderer_deadline: Option<Instant>,
+    literal_next: bool,
+    pasted: bool,
+}
+
+impl PasswordEditor {
+    /// Creates an editor showing the given feedback, with the default [`Keymap`].
+    pub fn new(feedback: PasswordFeedback) -> PasswordEditor {
+        PasswordEditor {
+            feedback: Some(feedback),
+            ..PasswordEditor::with_output(renderer(feedback), Keymap::default(), true)
+        }
+    }
+
+    /// Creates an editor drawing the password with a custom [`FeedbackRenderer`], with the
+    /// default [`Keymap`].
+    pub fn with_renderer(renderer: impl FeedbackRenderer + 'static) -> PasswordEditor {
+        PasswordEditor::with_output(Box::new(renderer), Keymap::default(), true)
+    }
+
+    /// Creates an editor that only returns output if `renders_output` is true, which is the case
+    /// when reading from a terminal.
+    pub(crate) fn with_output(
+        renderer: Box<dyn FeedbackRenderer>,
+        keymap: Keymap,
+        renders_output: bool,
+    ) -> PasswordEditor {
+        PasswordEditor {
+            state: FeedbackState::with_renderer(renderer, renders_output),
+            feedback: None,
+            keymap,
+            paste_policy: PastePolicy::default(),
+            reveal_timeout: DEFAULT_REVEAL_TIMEOUT,
+            clock: Arc::new(Instant::now),
+            conceal_at: None,
+            renderer_deadline: None,
+            literal_next: false,
+            pasted: false,
+        }
+    }
+
+    /// Sets the key bindings, see [`Keymap`].
+    pub fn keymap(self, keymap: Keymap) -> PasswordEditor {
+        PasswordEditor { keymap, ..self }
+    }
+
+    /// Sets what happens when text is pasted, see [`PastePolicy`].
+    pub fn paste_policy(self, paste_policy: PastePolicy) -> PasswordEditor {
+        PasswordEditor {
+            paste_policy,
+            ..self
+        }
+    }
+
+    /// Sets what the output leaves on screen once the password is submitted or typing is
+    /// cancelled, see [`AfterSubmit`].
+    pub fn after_submit(mut self, after_submit: AfterSubmit) -> PasswordEditor {
+        self.state.set_after_submit(after_submit);
+        self
+    }
+
+    /// Sets the styles of the output and the hint drawn with the prompt, see [`Theme`]. The mask
+    /// of the theme replaces the char of the [`PasswordFeedback`] the editor was created with,
+    /// custom renderers drawing their own masks.
+    pub fn theme(mut self, theme: Theme) -> PasswordEditor {
+        if let Some(feedback) = self.feedback
+            && theme.mask.is_some()
+        {
+            self.state
+                .set_renderer(renderer_with_mask(feedback, theme.mask.clone()));
+        }
+        self.state.set_theme(theme);
+        self
+    }
+
+    /// Sets what the terminal supports, which decides how the feedback is erased and redrawn
+    pub(crate) fn capabilities(mut self, capabilities: Capabilities) -> PasswordEditor {
+        self.state.set_capabilities(capabilities);
+        self
+    }
+
+    /// Sets how long the password stays in plaintext after [`Action::Reveal`], 5 seconds by
+    /// default. Use [`Duration::MAX`] to keep it revealed until the action is triggered again.
+    pub fn reveal_timeout(self, reveal_timeout: Duration) -> PasswordEditor {
+        PasswordEditor {
+            reveal_timeout,
+            ..self
+        }
+    }
+
+    /// Sets how many columns the terminal has, so that the output moves the cursor properly when
+    /// the password wraps onto several rows.
+    pub fn terminal_width(mut self, columns: usize) -> PasswordEditor {
+        self.state.set_terminal_width(columns);
+        self
+    }
+
+    /// Sets the prompt written before the password, so that the editor knows where the password
+    /// starts and can draw the prompt again, see [`PasswordEditor::resize`] and
+    /// [`Action::ClearScreen`]. The prompt itself is not part of the output.
+    pub fn prompt(mut self, prompt: impl ToString) -> PasswordEditor {
+        self.state.set_prompt(prompt.to_string());
+        self
+    }
+
+    /// Returns the output drawing the prompt set with [`PasswordEditor::prompt`], styled with the
+    /// theme, to be written before any key is handled.
+    pub fn prompt_output(&self) -> String {
+        self.state.prompt_output()
+    }
+
+    /// Tells the editor the terminal now has `columns` columns, returning the output that draws
+    /// the prompt and the password again.
+    pub fn resize(&mut self, columns: usize) -> Edit {
+        edit(self.state.resize(columns), EditStatus::Editing)
+    }
+
+    /// Returns the output that moves below the password before the program is suspended, leaving
+    /// the password as drawn.
+    pub fn suspend(&mut self) -> Edit {
+        edit(self.state.suspend(), EditStatus::Editing)
+    }
+
+    /// Returns the output that draws the prompt and the password again from the start of the line,
+    /// once the program is resumed, the terminal now having `columns` columns if known.
+    pub fn resume(&mut self, columns: Option<usize>) -> Edit {
+        edit(self.state.resume(columns), EditStatus::Editing)
+    }
+
+    #[cfg(test)]
+    pub(crate) fn clock(self, clock: Clock) -> PasswordEditor {
+        PasswordEditor { clock, ..self }
+    }
+
+    /// Returns when [`PasswordEditor::handle_timeout`] should be called next, if ever.
+    ///
+    /// Event loops should wait for keys until then, e.g. so that a revealed password gets masked
+    /// again even if the user stops typing.
+    pub fn deadline(&self) -> Option<Instant> {
+        match (self.conceal_at, self.renderer_deadline) {
+            (Some(conceal_at), Some(renderer_deadline)) => Some(conceal_at.min(renderer_deadline)),
+            (conceal_at, renderer_deadline) => conceal_at.or(renderer_deadline),
+        }
+    }
+
+    /// Updates the feedback once the [`PasswordEditor::deadline`] is reached, does nothing before.
+    pub fn handle_timeout(&mut self) -> Edit {
+        let now = (self.clock)();
+        let mut output = String::new();
+        if self
+            .renderer_deadline
+            .is_some_and(|renderer_deadline| now >= renderer_deadline)
+        {
+            output.push_str(&self.state.time_out());
+            self.renderer_deadline = self
+                .state
+                .timeout()
+                .and_then(|timeout| now.checked_add(timeout));
+        }
+        if self.conceal_at.is_some_and(|conceal_at| now >= conceal_at) {
+            self.conceal_at = None;
+            output.push_str(&self.state.toggle_reveal());
+        }
+
+        edit(output, EditStatus::Editing)
+    }
+
+    /// Applies the key to the password.
+    pub fn handle_key(&mut self, key: Key) -> Edit {
+        let mut output = self.state.key_pressed();
+        let mut edit = self.apply(key);
+        output.push_str(&edit.output);
+        edit.output = output;
+
+        self.renderer_deadline = self
+            .state
+            .timeout()
+            .and_then(|timeout| (self.clock)().checked_add(timeout));
+        edit
+    }
+
+    fn apply(&mut self, key: Key) -> Edit {
+        let action = if self.literal_next {
+            self.literal_next = false;
+            Action::AcceptAsText
+        } else {
+            self.keymap.action(&key)
+        };
+
+        let state = &mut self.state;
+        let output = match action {
+            Action::Submit => return edit(state.finish(), EditStatus::Submitted),
+            Action::Cancel => return edit(state.abort(), EditStatus::Cancelled),
+            Action::Suspend => return edit(String::new(), EditStatus::Suspended),
+            Action::EndOfFile if state.is_empty() => {
+                return edit(String::new(), EditStatus::EndOfFile);
+            }
+            Action::EndOfFile | Action::EraseCharForward => state.delete_char(),
+            Action::EraseChar => state.pop_char(),
+            Action::EraseWord => state.clear_til_last_space(),
+            Action::EraseAlphanumericWord => state.delete_word_backward(),
+            Action::EraseWordForward => state.delete_word_forward(),
+            Action::EraseLine => state.clear(),
+            Action::EraseToEnd => state.kill_to_end(),
+            Action::MoveLeft => state.move_left(),
+            Action::MoveRight => state.move_right(),
+            Action::MoveHome => state.move_home(),
+            Action::MoveEnd => state.move_end(),
+            Action::MoveWordLeft => state.move_word_left(),
+            Action::MoveWordRight => state.move_word_right(),
+            Action::ClearScreen => state.clear_screen(),
+            Action::Reveal => {
+                let output = state.toggle_reveal();
+                self.conceal_at = if state.is_revealed() {
+                    (self.clock)().checked_add(self.reveal_timeout)
+                } else {
+                    None
+                };
+                output
+            }
+            Action::LiteralNext => {
+                self.literal_next = true;
+                String::new()
+            }
+            Action::AcceptAsText => match key {
+                Key::Paste(text) => {
+                    let mut text = SafeString::from_string(text);
+                    match self.paste_policy {
+                        PastePolicy::Reject(_) => {
+                            return edit(state.abort(), EditStatus::PasteRejected);
+                        }
+                        PastePolicy::StripTrailingNewlines => {
+                            let len = text.trim_end_matches(['\r', '\n']).len();
+                            text.truncate(len);
+                        }
+                        PastePolicy::StripControlCharacters => {
+                            text.retain(|c| !c.is_control());
+                        }
+                        PastePolicy::Allow => {}
+                    }
+                    if !text.is_empty() {
+                        self.pasted = true;
+                    }
+                    state.insert_str(&text)
+                }
+                key => match key.as_char() {
+                    Some(c) => state.push_char(c),
+                    None => String::new(),
+                },
+            },
+            Action::Ignore => String::new(),
+        };
+
+        edit(output, EditStatus::Editing)
+    }
+
+    /// Returns what the password looks like on screen with the configured feedback.
+    pub fn display(&self) -> String {
+        self.state.display()
+    }
+
+    /// Returns the position of the cursor in [`PasswordEditor::display`], in terminal cells, wide
+    /// chars such as CJK ideographs taking two cells.
+    pub fn cursor(&self) -> usize {
+        self.state.display_cursor()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.state.is_empty()
+    }
+
+    /// Returns whether text was pasted into the password, as opposed to only typed.
+    pub fn was_pasted(&self) -> bool {
+        self.pasted
+    }
+
+    /// Returns the password typed so far.
+    pub fn into_password(self) -> String {
+        self.state.into_password()
+    }
+}
+
+fn edit(output: String, status: EditStatus) -> Edit {
+    Edit { output, status }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{PasswordFeedback, PastePolicy};
+    use crate::editor::{EditStatus, PasswordEditor};
+    use crate::key::Key;
+    use crate::keymap::{Action, Keymap};
+    use crate::theme::Theme;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn password_editor_outputs() {
+        let mut editor = PasswordEditor::new(PasswordFeedback::Mask('*'));
+        assert_eq!(editor.handle_key(Key::Char('a')).output, "*");
+        assert_eq!(editor.handle_key(Key::Char('b')).output, "*");
+        assert_eq!(editor.handle_key(Key::Home).output, "\x08\x08");
+        assert_eq!(editor.handle_key(Key::Delete).output, "* \x08\x08");
+        assert_eq!(editor.handle_key(Key::Ctrl('v')).output, "");
+        assert_eq!(editor.handle_key(Key::Tab).output, "**\x08");
+
+        let edit = editor.handle_key(Key::Enter);
+        assert_eq!(edit.output, "\n");
+        assert_eq!(edit.status, EditStatus::Submitted);
+        assert_eq!(editor.into_password(), "\tb");
+    }
+
+    #[test]
+    fn password_editor_suspend() {
+        let mut editor = PasswordEditor::new(PasswordFeedback::Mask('*')).prompt("> ");
+        editor.handle_key(Key::Char('a'));
+        let edit = editor.handle_key(Key::Ctrl('z'));
+        assert_eq!(edit.status, EditStatus::Suspended);
+        assert_eq!(edit.output, "");
+        assert_eq!(editor.suspend().output, "\n");
+        assert_eq!(editor.resume(None).output, "> *");
+        editor.handle_key(Key::Char('b'));
+        assert_eq!(editor.into_password(), "ab");
+    }
+
+    #[test]
+    fn password_editor_theme_mask() {
+        let mut editor =
+            PasswordEditor::new(PasswordFeedback::Mask('*')).theme(Theme::new().mask("••"));
+        editor.handle_key(Key::Char('a'));
+        editor.handle_key(Key::Char('b'));
+        assert_eq!(editor.display(), "••••");
+        assert_eq!(editor.cursor(), 4);
+        assert_eq!(
+            editor.handle_key(Key::Backspace).output,
+            "\x08 \x08\x08 \x08"
+        );
+    }
+
+    #[test]
+    fn password_editor_theme_replaces_random_mask_while_typing() {
+        let mut editor = PasswordEditor::new(PasswordFeedback::RandomMask('*'));
+        editor.handle_key(Key::Paste("abc".to_string()));
+        let mut editor = editor.theme(Theme::new().mask("#"));
+        let masks = editor.display();
+        assert!((3..=9).contains(&masks.len()));
+        assert!(masks.chars().all(|c| c == '#'));
+
+        editor.handle_key(Key::Home);
+        editor.handle_key(Key::Delete);
+        editor.handle_key(Key::End);
+        editor.handle_key(Key::Backspace);
+        editor.handle_key(Key::Char('d'));
+        assert_eq!(editor.into_password(), "bd");
+    }
+
+    #[test]
+    fn password_editor_display() {
+        let mut editor = PasswordEditor::new(PasswordFeedback::PartialMask('*', 1));
+        editor.handle_key(Key::Char('a'));
+        editor.handle_key(Key::Char('b'));
+        editor.handle_key(Key::Left);
+        assert_eq!(editor.display(), "a*");
+        assert_eq!(editor.cursor(), 1);
+
+        let mut editor = PasswordEditor::new(PasswordFeedback::Hide);
+        editor.handle_key(Key::Char('a'));
+        assert_eq!(editor.display(), "");
+        assert_eq!(editor.cursor(), 0);
+    }
+
+    #[test]
+    fn password_editor_statuses() {
+        let mut editor = PasswordEditor::new(PasswordFeedback::Hide);
+        assert_eq!(
+            editor.handle_key(Key::Ctrl('d')).status,
+            EditStatus::EndOfFile
+        );
+        assert_eq!(
+            editor.handle_key(Key::Char('a')).status,
+            EditStatus::Editing
+        );
+        assert_eq!(
+            editor.handle_key(Key::Ctrl('d')).status,
+            EditStatus::Editing
+        );
+        assert_eq!(
+            editor.handle_key(Key::Ctrl('c')).status,
+            EditStatus::Cancelled
+        );
+        assert!(editor.is_empty());
+
+        let mut editor = PasswordEditor::new(PasswordFeedback::Hide)
+            .keymap(Keymap::minimal().bind(Key::Esc, Action::Cancel));
+        assert_eq!(editor.handle_key(Key::Esc).status, EditStatus::Cancelled);
+    }
+
+    #[test]
+    fn password_editor_paste() {
+        let mut editor = PasswordEditor::new(PasswordFeedback::Mask('*'));
+        editor.handle_key(Key::Char('a'));
+        assert!(!editor.was_pasted());
+        let edit = editor.handle_key(Key::Paste("b\tc \r\n".to_string()));
+        assert_eq!(edit.output, "****");
+        assert_eq!(edit.status, EditStatus::Editing);
+        assert!(editor.was_pasted());
+        assert_eq!(editor.into_password(), "ab\tc ");
+
+        let mut editor = PasswordEditor::new(PasswordFeedback::Hide)
+            .paste_policy(PastePolicy::StripControlCharacters);
+        editor.handle_key(Key::Paste("b\tc \r\n".to_string()));
+        assert_eq!(editor.into_password(), "bc ");
+
+        let mut editor =
+            PasswordEditor::new(PasswordFeedback::Hide).paste_policy(PastePolicy::Allow);
+        editor.handle_key(Key::Paste("b\tc\n".to_string()));
+        assert_eq!(editor.into_password(), "b\tc\n");
+
+        let mut editor = PasswordEditor::new(PasswordFeedback::Hide)
+            .paste_policy(PastePolicy::Reject("no".to_string()));
+        editor.handle_key(Key::Char('a'));
+        let edit = editor.handle_key(Key::Paste("b".to_string()));
+        assert_eq!(edit.status, EditStatus::PasteRejected);
+        assert!(editor.is_empty());
+    }
+
+    #[test]
+    fn password_editor_reveal_times_out() {
+        let now = Arc::new(Mutex::new(Instant::now()));
+        let clock = now.clone();
+        let mut editor = PasswordEditor::new(PasswordFeedback::Mask('*'))
+            .keymap(Keymap::default().bind(Key::Ctrl('r'), Action::Reveal))
+            .reveal_timeout(Duration::from_secs(5))
+            .clock(Arc::new(move || *clock.lock().unwrap()));
+        editor.handle_key(Key::Char('a'));
+        editor.handle_key(Key::Char('b'));
+        assert_eq!(editor.deadline(), None);
+
+        assert_eq!(editor.handle_key(Key::Ctrl('r')).output, "\x08\x08ab");
+        assert_eq!(editor.display(), "ab");
+        let deadline = editor.deadline().unwrap();
+        assert_eq!(deadline, *now.lock().unwrap() + Duration::from_secs(5));
+
+        *now.lock().unwrap() += Duration::from_secs(4);
+        assert_eq!(editor.handle_timeout().output, "");
+        assert_eq!(editor.display(), "ab");
+
+        *now.lock().unwrap() += Duration::from_secs(1);
+        assert_eq!(editor.handle_timeout().output, "\x08\x08**");
+        assert_eq!(editor.display(), "**");
+        assert_eq!(editor.deadline(), None);
+
+        editor.handle_key(Key::Ctrl('r'));
+        editor.handle_key(Key::Ctrl('r'));
+        assert_eq!(editor.display(), "**");
+        assert_eq!(editor.deadline(), None);
+    }
+
+    #[test]
+    fn password_editor_flash_last() {
+        let now = Arc::new(Mutex::new(Instant::now()));
+        let clock = now.clone();
+        let timeout = Duration::from_millis(500);
+        let mut editor = PasswordEditor::new(PasswordFeedback::FlashLast('*', timeout))
+            .clock(Arc::new(move || *clock.lock().unwrap()));
+
+        assert_eq!(editor.handle_key(Key::Char('a')).output, "a");
+        assert_eq!(editor.deadline(), Some(*now.lock().unwrap() + timeout));
+        assert_eq!(editor.handle_key(Key::Char('b')).output, "\x08*b");
+        assert_eq!(editor.display(), "*b");
+
+        *now.lock().unwrap() += Duration::from_millis(499);
+        assert_eq!(editor.handle_timeout().output, "");
+        *now.lock().unwrap() += Duration::from_millis(1);
+        assert_eq!(editor.handle_timeout().output, "\x08*");
+        assert_eq!(editor.display(), "**");
+        assert_eq!(editor.deadline(), None);
+
+        editor.handle_key(Key::Char('c'));
+        assert_eq!(editor.handle_key(Key::Left).output, "\x08*\x08");
+        assert_eq!(editor.deadline(), None);
+
+        editor.handle_key(Key::End);
+        editor.handle_key(Key::Char('d'));
+        assert_eq!(editor.handle_key(Key::Enter).output, "\x08*\n");
+        assert_eq!(editor.into_password(), "abcd");
+    }
+}
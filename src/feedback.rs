@@ -1,139 +1,880 @@
-use crate::config::PasswordFeedback;
+use crate::config::AfterSubmit;
+use crate::renderer::{CharFeedback, FeedbackEvent, FeedbackRenderer};
+use crate::terminfo::Capabilities;
+use crate::theme::Theme;
 use rtoolbox::safe_string::SafeString;
-use std::cmp::min;
+use std::time::Duration;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
 pub struct FeedbackState {
     password: SafeString,
+    /// Position of the cursor, in graphemes from the start of the password, graphemes being what
+    /// users see as a single char, e.g. an emoji made of several code points
+    cursor: usize,
     needs_terminal_configuration: bool,
-    displayed_count: usize,
-    feedback: PasswordFeedback,
+    /// Number of cells drawn on screen
+    displayed_width: usize,
+    /// Position of the terminal cursor, in cells from the start of what was drawn
+    displayed_cursor: usize,
+    /// Status drawn by the renderer, if it draws one rather than the chars
+    displayed_status: Option<String>,
+    renderer: Box<dyn FeedbackRenderer>,
+    /// Whether the password is currently shown in plaintext, regardless of the feedback
+    revealed: bool,
+    /// Width of the terminal in cells, if known, so that the cursor can move across wrapped rows
+    columns: Option<usize>,
+    /// Text drawn before the password, so that it can be drawn again when repainting
+    prompt: String,
+    /// Column of the terminal where the password starts, i.e. after the last line of the prompt
+    start_column: usize,
+    /// What is left on screen once the password is submitted or typing is cancelled
+    after_submit: AfterSubmit,
+    /// Styles of the output, the mask of the theme being handled by the renderer
+    theme: Theme,
+    /// What the terminal supports, which decides how the feedback is erased and redrawn
+    capabilities: Capabilities,
 }
 
 impl FeedbackState {
-    pub fn new(feedback: PasswordFeedback, needs_terminal_configuration: bool) -> Self {
+    #[cfg(test)]
+    pub fn new(
+        feedback: crate::config::PasswordFeedback,
+        needs_terminal_configuration: bool,
+    ) -> Self {
+        FeedbackState::with_renderer(
+            crate::renderer::renderer(feedback),
+            needs_terminal_configuration,
+        )
+    }
+
+    pub fn with_renderer(
+        renderer: Box<dyn FeedbackRenderer>,
+        needs_terminal_configuration: bool,
+    ) -> Self {
         FeedbackState {
             password: SafeString::new(),
+            cursor: 0,
             needs_terminal_configuration,
-            displayed_count: 0,
-            feedback,
+            displayed_width: 0,
+            displayed_cursor: 0,
+            displayed_status: None,
+            renderer,
+            revealed: false,
+            columns: None,
+            prompt: String::new(),
+            start_column: 0,
+            after_submit: AfterSubmit::default(),
+            theme: Theme::default(),
+            capabilities: Capabilities::ansi(),
         }
     }
 
-    pub fn push_char(&mut self, c: char) -> String {
-        self.password.push(c);
+    /// Sets the width of the terminal, so that long passwords wrapping onto several rows are
+    /// erased properly
+    pub fn set_terminal_width(&mut self, columns: usize) {
+        self.columns = Some(columns).filter(|&columns| columns > 0);
+    }
+
+    /// Replaces the renderer, telling it about the chars already typed, if any
+    pub fn set_renderer(&mut self, renderer: Box<dyn FeedbackRenderer>) {
+        self.renderer = renderer;
+        self.renderer
+            .handle_event(FeedbackEvent::Cleared, self.grapheme_count());
+    }
+
+    pub fn set_after_submit(&mut self, after_submit: AfterSubmit) {
+        self.after_submit = after_submit;
+    }
+
+    pub fn set_capabilities(&mut self, capabilities: Capabilities) {
+        self.capabilities = capabilities;
+    }
+
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+    }
 
+    /// Sets the prompt written before the password
+    pub fn set_prompt(&mut self, prompt: String) {
+        self.prompt = prompt;
+        self.start_column = self.prompt_last_line().width();
+    }
+
+    /// Draws the last line of the prompt and the password again in place, e.g. after the
+    /// terminal was resized to `columns`
+    ///
+    /// Terminals reflow wrapped rows when resized, so the rows are counted with the new width.
+    pub fn resize(&mut self, columns: usize) -> String {
+        self.set_terminal_width(columns);
+
+        // Rows are not tracked without cursor movement, so nothing moved
+        if !self.needs_terminal_configuration || !self.capabilities.cursor_movement {
+            return String::new();
+        }
+
+        let mut output = self.erase_line();
+        output.push_str(&self.draw_prompt(self.prompt_last_line()));
+        output.push_str(&self.redraw_all());
+        output
+    }
+
+    /// Returns the output drawing the prompt, before anything is typed
+    pub fn prompt_output(&self) -> String {
+        self.draw_prompt(&self.prompt)
+    }
+
+    /// Clears the screen, then draws the prompt and the password again at the top (Ctrl-L)
+    pub fn clear_screen(&mut self) -> String {
+        self.renderer
+            .handle_event(FeedbackEvent::Cleared, self.grapheme_count());
         if !self.needs_terminal_configuration {
             return String::new();
         }
 
-        match self.feedback {
-            PasswordFeedback::Hide => String::new(),
-            PasswordFeedback::Mask(mask) => {
-                self.displayed_count += 1;
-                mask.to_string()
-            }
-            PasswordFeedback::PartialMask(mask, n) => {
-                self.displayed_count += 1;
-                if self.displayed_count <= n {
-                    c.to_string()
-                } else {
-                    mask.to_string()
-                }
-            }
+        // Without escape sequences, the prompt is drawn again on the next line instead
+        let mut output = if self.capabilities.cursor_movement {
+            "\x1B[H\x1B[2J".to_string()
+        } else {
+            "\n".to_string()
+        };
+        output.push_str(&self.draw_prompt(&self.prompt));
+        output.push_str(&self.redraw_all());
+        output
+    }
+
+    /// Leaves the password on screen and moves below it, before the program is suspended (Ctrl-Z)
+    pub fn suspend(&mut self) -> String {
+        if !self.needs_terminal_configuration {
+            return String::new();
+        }
+
+        // Editing goes on where it was once the password is drawn again
+        let cursor = self.cursor;
+        let output = self.move_below();
+        self.cursor = cursor;
+        output
+    }
+
+    /// Draws the prompt and the password again from the start of the line, once the program is
+    /// resumed, with the width of the terminal, which may have changed meanwhile
+    pub fn resume(&mut self, columns: Option<usize>) -> String {
+        if let Some(columns) = columns {
+            self.set_terminal_width(columns);
+        }
+        if !self.needs_terminal_configuration {
+            return String::new();
         }
+
+        let mut output = self.draw_prompt(&self.prompt);
+        output.push_str(&self.redraw_all());
+        output
+    }
+
+    /// Inserts a char at the cursor and moves the cursor after it
+    pub fn push_char(&mut self, c: char) -> String {
+        self.insert(c.encode_utf8(&mut [0; 4]), false)
+    }
+
+    /// Inserts text at the cursor as a single edit, e.g. when it is pasted
+    pub fn insert_str(&mut self, text: &str) -> String {
+        self.insert(text, true)
+    }
+
+    /// Tells the renderer a key was pressed, before applying it
+    pub fn key_pressed(&mut self) -> String {
+        let before = self.layout();
+        self.notify(&[FeedbackEvent::KeyPressed], None, before)
+    }
+
+    /// Returns after how long without any key [`FeedbackState::time_out`] should be called
+    pub fn timeout(&self) -> Option<Duration> {
+        self.renderer.timeout()
+    }
+
+    /// Tells the renderer its timeout has passed
+    pub fn time_out(&mut self) -> String {
+        let before = self.layout();
+        self.notify(&[FeedbackEvent::TimedOut], None, before)
     }
 
+    /// Deletes the char before the cursor (Backspace)
     pub fn pop_char(&mut self) -> String {
-        let last_char = self.password.chars().last();
-        if let Some(c) = last_char {
-            let new_len = self.password.len() - c.len_utf8();
-            self.password.truncate(new_len);
+        if self.cursor == 0 {
+            return String::new();
+        }
 
-            if !self.needs_terminal_configuration {
-                return String::new();
-            }
+        self.delete_range(self.cursor - 1, self.cursor)
+    }
 
-            if self.displayed_count > 0 {
-                self.displayed_count -= 1;
-                "\x08 \x08".to_string()
-            } else {
-                String::new()
-            }
-        } else {
-            String::new()
+    /// Deletes the char under the cursor (Delete)
+    pub fn delete_char(&mut self) -> String {
+        if self.cursor == self.grapheme_count() {
+            return String::new();
         }
+
+        self.delete_range(self.cursor, self.cursor + 1)
     }
 
+    /// Deletes the whole password, wherever the cursor is
     pub fn clear(&mut self) -> String {
+        let before = self.layout();
         self.password = SafeString::new();
+        self.cursor = 0;
+        self.notify(&[FeedbackEvent::Cleared], Some(0), before)
+    }
+
+    /// Deletes from the cursor to the end of the password (Ctrl-K)
+    pub fn kill_to_end(&mut self) -> String {
+        self.delete_range(self.cursor, self.grapheme_count())
+    }
+
+    /// Deletes the whitespace-delimited word before the cursor (Ctrl-W)
+    pub fn clear_til_last_space(&mut self) -> String {
+        let start = self.unix_word_start();
+        self.delete_range(start, self.cursor)
+    }
+
+    /// Deletes the alphanumeric word before the cursor (Alt-Backspace)
+    pub fn delete_word_backward(&mut self) -> String {
+        let start = self.word_start();
+        self.delete_range(start, self.cursor)
+    }
+
+    /// Deletes the alphanumeric word after the cursor (Alt-D)
+    pub fn delete_word_forward(&mut self) -> String {
+        let end = self.word_end();
+        self.delete_range(self.cursor, end)
+    }
+
+    pub fn move_left(&mut self) -> String {
+        self.move_to(self.cursor.saturating_sub(1))
+    }
+
+    pub fn move_right(&mut self) -> String {
+        self.move_to(self.cursor + 1)
+    }
+
+    pub fn move_home(&mut self) -> String {
+        self.move_to(0)
+    }
+
+    pub fn move_end(&mut self) -> String {
+        self.move_to(self.grapheme_count())
+    }
+
+    /// Moves to the start of the alphanumeric word before the cursor (Alt-B)
+    pub fn move_word_left(&mut self) -> String {
+        self.move_to(self.word_start())
+    }
+
+    /// Moves to the end of the alphanumeric word after the cursor (Alt-F)
+    pub fn move_word_right(&mut self) -> String {
+        self.move_to(self.word_end())
+    }
+
+    pub fn is_revealed(&self) -> bool {
+        self.revealed
+    }
+
+    /// Switches between the configured feedback and plaintext, redrawing the whole password
+    pub fn toggle_reveal(&mut self) -> String {
+        self.revealed = !self.revealed;
 
         if !self.needs_terminal_configuration {
             return String::new();
         }
 
-        let count = self.displayed_count;
-        self.displayed_count = 0;
-        "\x08 \x08".repeat(count).to_string()
+        match self.status() {
+            Some(status) => self.redraw_status(status),
+            None => self.redraw_from(0),
+        }
     }
 
-    pub fn clear_til_last_space(&mut self) -> String {
-        let mut trimmed = self.password.as_str().trim_end();
+    pub fn abort(&mut self) -> String {
+        let output = self.leave_line(false);
+        self.password = SafeString::new();
+        self.cursor = 0;
+        self.renderer.handle_event(FeedbackEvent::Aborted, 0);
 
-        match trimmed.rfind(' ') {
-            Some(last_space_position) => {
-                trimmed = &trimmed[..=last_space_position];
-            }
-            None => {
-                trimmed = "";
+        self.displayed_width = 0;
+        self.displayed_cursor = 0;
+        self.displayed_status = None;
+        output
+    }
+
+    pub fn finish(&mut self) -> String {
+        self.renderer
+            .handle_event(FeedbackEvent::Finished, self.grapheme_count());
+        self.leave_line(true)
+    }
+
+    /// Returns what the whole password looks like on screen
+    pub fn display(&self) -> String {
+        match self.status() {
+            Some(status) => status,
+            None => self.render(0, self.grapheme_count(), false),
+        }
+    }
+
+    /// Returns the position of the cursor on screen, in terminal cells from the start of the
+    /// display
+    pub fn display_cursor(&self) -> usize {
+        match self.status() {
+            Some(status) => status.width(),
+            None => self.width(0, self.cursor),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.password.is_empty()
+    }
+
+    pub fn into_password(self) -> String {
+        self.password.into_inner()
+    }
+
+    fn grapheme_count(&self) -> usize {
+        self.password.graphemes(true).count()
+    }
+
+    fn byte_offset(&self, position: usize) -> usize {
+        self.password
+            .grapheme_indices(true)
+            .nth(position)
+            .map_or(self.password.len(), |(offset, _)| offset)
+    }
+
+    /// Start of the word before the cursor, words being separated by whitespace
+    fn unix_word_start(&self) -> usize {
+        let before: Vec<&str> = self.password.graphemes(true).take(self.cursor).collect();
+        let mut start = before.len();
+        while start > 0 && is_whitespace(before[start - 1]) {
+            start -= 1;
+        }
+        while start > 0 && !is_whitespace(before[start - 1]) {
+            start -= 1;
+        }
+        start
+    }
+
+    /// Start of the word before the cursor, words being made of alphanumeric chars
+    fn word_start(&self) -> usize {
+        let before: Vec<&str> = self.password.graphemes(true).take(self.cursor).collect();
+        let mut start = before.len();
+        while start > 0 && !is_alphanumeric(before[start - 1]) {
+            start -= 1;
+        }
+        while start > 0 && is_alphanumeric(before[start - 1]) {
+            start -= 1;
+        }
+        start
+    }
+
+    /// End of the word after the cursor, words being made of alphanumeric chars
+    fn word_end(&self) -> usize {
+        let mut after = self.password.graphemes(true).skip(self.cursor).peekable();
+        let mut end = self.cursor;
+        while after.next_if(|g| !is_alphanumeric(g)).is_some() {
+            end += 1;
+        }
+        while after.next_if(|g| is_alphanumeric(g)).is_some() {
+            end += 1;
+        }
+        end
+    }
+
+    /// Inserts text at the cursor and moves the cursor after it
+    fn insert(&mut self, text: &str, pasted: bool) -> String {
+        self.replace(self.cursor, self.cursor, text, pasted)
+    }
+
+    fn delete_range(&mut self, start: usize, end: usize) -> String {
+        if start >= end {
+            return String::new();
+        }
+
+        self.replace(start, end, "", false)
+    }
+
+    /// Replaces the graphemes from `start` to `end` with `text` and moves the cursor after it
+    ///
+    /// The text may merge with the graphemes around it, e.g. a combining accent typed after a
+    /// letter, so the renderer is told which graphemes were really replaced.
+    fn replace(&mut self, start: usize, end: usize, text: &str, pasted: bool) -> String {
+        let before = self.layout();
+        let old_count = before.len();
+        let start_offset = self.byte_offset(start);
+        let end_offset = self.byte_offset(end);
+        self.password.replace_range(start_offset..end_offset, text);
+
+        let text_end = start_offset + text.len();
+        let bounds: Vec<(usize, usize)> = self
+            .password
+            .grapheme_indices(true)
+            .map(|(offset, grapheme)| (offset, offset + grapheme.len()))
+            .collect();
+        let kept_before = bounds
+            .iter()
+            .take_while(|&&(_, end)| end <= start_offset)
+            .count();
+        let kept_after = bounds
+            .iter()
+            .rev()
+            .take_while(|&&(start, _)| start >= text_end)
+            .count();
+        let removed = old_count - kept_before - kept_after;
+        let inserted = bounds.len() - kept_before - kept_after;
+        self.cursor = kept_before + inserted;
+
+        let mut events = Vec::new();
+        if removed > 0 {
+            events.push(FeedbackEvent::Deleted {
+                start: kept_before,
+                end: kept_before + removed,
+            });
+        }
+        if inserted > 0 {
+            events.push(FeedbackEvent::Inserted {
+                position: kept_before,
+                count: inserted,
+                pasted,
+            });
+        }
+        self.notify(&events, Some(kept_before), before)
+    }
+
+    fn move_to(&mut self, position: usize) -> String {
+        let position = position.min(self.grapheme_count());
+        let old_position = self.cursor;
+        self.cursor = position;
+
+        // There is no cursor to move within a status
+        if !self.needs_terminal_configuration || self.status().is_some() || position == old_position
+        {
+            return String::new();
+        }
+
+        let old_cursor = self.displayed_cursor;
+        self.displayed_cursor = self.width(0, position);
+        if position < old_position {
+            match self.capabilities.cursor_left {
+                Some(_) => self.move_back(old_cursor, self.displayed_cursor),
+                None => self.redraw_line(),
             }
+        } else {
+            // Moving right is done by writing over the cells we move across
+            let mut output = self.render(old_position, position, true);
+            output.push_str(self.wrap(self.displayed_cursor));
+            output
         }
+    }
 
-        let new_displayed_count = trimmed.chars().count();
-        let removed_chars = self.password.chars().count() - trimmed.chars().count();
-        self.password = trimmed.to_string().into();
+    /// Moves the cursor to the start of the line below the password
+    fn move_below(&mut self) -> String {
+        // The rows after the cursor would be drawn over otherwise
+        let mut output = match self.columns() {
+            Some(_) => self.move_to(self.grapheme_count()),
+            None => String::new(),
+        };
+        if self.wrap(self.displayed_width).is_empty() {
+            output.push('\n');
+        }
+        output
+    }
 
+    /// Leaves the line of the prompt once the password is `submitted` or typing is cancelled,
+    /// erasing or replacing it depending on [`AfterSubmit`]
+    fn leave_line(&mut self, submitted: bool) -> String {
         if !self.needs_terminal_configuration {
             return String::new();
         }
 
-        let count = self.displayed_count;
-        self.displayed_count = new_displayed_count;
-        "\x08 \x08".repeat(min(removed_chars, count)).to_string()
+        let replacement = match &self.after_submit {
+            AfterSubmit::Keep => return self.move_below(),
+            AfterSubmit::Erase => return self.erase_line(),
+            AfterSubmit::Replace {
+                submitted: text, ..
+            } if submitted => text,
+            AfterSubmit::Replace {
+                cancelled: text, ..
+            } => text,
+        };
+
+        let mut output = self.erase_line();
+        output.push_str(&self.theme.prompt_style.paint(self.prompt_last_line()));
+        output.push_str(replacement);
+        output.push('\n');
+        output
     }
 
-    pub fn abort(&mut self) -> String {
-        self.password = SafeString::new();
+    /// Erases the last line of the prompt and the password, leaving the cursor where the prompt
+    /// started
+    fn erase_line(&self) -> String {
+        if self.capabilities.cursor_movement && self.columns().is_some() {
+            return match self.row(self.displayed_cursor) {
+                0 => "\r\x1B[J".to_string(),
+                rows => format!("\x1B[{rows}A\r\x1B[J"),
+            };
+        }
+
+        let cells = self.start_column + self.displayed_width;
+        let capabilities = &self.capabilities;
+        match (&capabilities.carriage_return, &capabilities.cursor_left) {
+            (Some(cr), _) => match capabilities.clear_to_end {
+                Some(ref el) => format!("{cr}{el}"),
+                None => format!("{cr}{}{cr}", " ".repeat(cells)),
+            },
+            (None, Some(left)) => {
+                let back = left.repeat(self.start_column + self.displayed_cursor);
+                format!("{back}{}{}", " ".repeat(cells), left.repeat(cells))
+            }
+            // Nothing can be erased, at least leave the line
+            (None, None) => "\n".to_string(),
+        }
+    }
+
+    /// Tells the renderer about the events and redraws what they changed, `before` being the
+    /// layout from before the events, which left the graphemes before `edited` in place
+    fn notify(
+        &mut self,
+        events: &[FeedbackEvent],
+        edited: Option<usize>,
+        before: Vec<CharFeedback>,
+    ) -> String {
+        let count = self.grapheme_count();
+        let mut len = before.len();
+        for &event in events {
+            match event {
+                FeedbackEvent::Inserted {
+                    count: inserted, ..
+                } => len += inserted,
+                FeedbackEvent::Deleted { start, end } => len -= end - start,
+                FeedbackEvent::Cleared => len = count,
+                _ => {}
+            }
+            self.renderer.handle_event(event, len);
+        }
 
         if !self.needs_terminal_configuration {
             return String::new();
         }
 
-        self.displayed_count = 0;
-        '\n'.to_string()
+        if let Some(status) = self.status() {
+            return self.redraw_status(status);
+        }
+
+        // The graphemes before the edit are where they were, but the renderer may draw them
+        // differently
+        let after = self.layout();
+        let changed = (0..edited.unwrap_or(count))
+            .find(|&position| before[position] != after[position])
+            .or(edited);
+        match changed {
+            Some(position) => self.redraw_from(position),
+            None => String::new(),
+        }
     }
 
-    pub fn finish(&mut self) -> String {
-        if !self.needs_terminal_configuration {
+    /// Returns the status drawn in place of the password, if any
+    fn status(&self) -> Option<String> {
+        if self.revealed {
+            return None;
+        }
+        self.renderer.render_status(self.grapheme_count())
+    }
+
+    /// Returns how each char is drawn
+    fn layout(&self) -> Vec<CharFeedback> {
+        let count = self.grapheme_count();
+        (0..count)
+            .map(|position| self.char_feedback(position, count))
+            .collect()
+    }
+
+    fn char_feedback(&self, position: usize, count: usize) -> CharFeedback {
+        if self.revealed {
+            return CharFeedback::Plaintext;
+        }
+        self.renderer.render_char(position, count)
+    }
+
+    /// Renders what the chars between `start` and `end` look like on screen, with the style of
+    /// the theme if `styled`
+    fn render(&self, start: usize, end: usize, styled: bool) -> String {
+        let count = self.grapheme_count();
+        let style = &self.theme.mask_style;
+        let mut output = String::new();
+        // Consecutive masks share the escape sequences of the style
+        let mut in_mask = false;
+        for (position, grapheme) in self
+            .password
+            .graphemes(true)
+            .enumerate()
+            .skip(start)
+            .take(end - start)
+        {
+            match self.char_feedback(position, count) {
+                CharFeedback::Hidden => {}
+                CharFeedback::Plaintext => {
+                    if in_mask {
+                        output.push_str(style.end());
+                        in_mask = false;
+                    }
+                    output.push_str(grapheme);
+                }
+                CharFeedback::Mask(mask) => {
+                    if styled && !in_mask && !mask.is_empty() {
+                        output.push_str(&style.start());
+                        in_mask = true;
+                    }
+                    output.push_str(&mask);
+                }
+            }
+        }
+        if in_mask {
+            output.push_str(style.end());
+        }
+        output
+    }
+
+    fn prompt_last_line(&self) -> &str {
+        self.prompt.rsplit(['\r', '\n']).next().unwrap_or_default()
+    }
+
+    /// Draws the prompt, or its last line, followed by the hint of the theme, leaving the cursor
+    /// where the password starts
+    fn draw_prompt(&self, prompt: &str) -> String {
+        let mut output = self.theme.prompt_style.paint(prompt);
+        if let Some(ref hint) = self.theme.hint
+            && let Some(columns) = self.columns()
+        {
+            let start = self.row_and_column(0).1;
+            // Keep at least a cell between the prompt and the hint
+            if start + 1 + hint.width() <= columns {
+                output.push_str(&format!(
+                    "\x1B[{}G{}\x1B[{}G",
+                    columns - hint.width() + 1,
+                    self.theme.hint_style.paint(hint),
+                    start + 1
+                ));
+            }
+        }
+        output
+    }
+
+    /// Returns the number of terminal cells taken by the graphemes between `start` and `end`
+    fn width(&self, start: usize, end: usize) -> usize {
+        let count = self.grapheme_count();
+        self.password
+            .graphemes(true)
+            .enumerate()
+            .skip(start)
+            .take(end - start)
+            .map(
+                |(position, grapheme)| match self.char_feedback(position, count) {
+                    CharFeedback::Hidden => 0,
+                    CharFeedback::Plaintext => grapheme.width(),
+                    CharFeedback::Mask(mask) => mask.width(),
+                },
+            )
+            .sum()
+    }
+
+    /// Draws the status over whatever was drawn before, unless it is already on screen
+    fn redraw_status(&mut self, status: String) -> String {
+        if self.displayed_status.as_ref() == Some(&status) {
             return String::new();
         }
 
-        '\n'.to_string()
+        if self.capabilities.cursor_left.is_none() {
+            return self.redraw_line();
+        }
+
+        let width = status.width();
+        let mut output = self.move_back(self.displayed_cursor, 0);
+        output.push_str(&self.theme.mask_style.paint(&status));
+        let erased = self.displayed_width.saturating_sub(width);
+        output.push_str(&" ".repeat(erased));
+        output.push_str(self.wrap(width + erased));
+        output.push_str(&self.move_back(width + erased, width));
+        self.displayed_width = width;
+        self.displayed_cursor = width;
+        self.displayed_status = Some(status);
+        output
     }
 
-    pub fn is_empty(&self) -> bool {
-        self.password.is_empty()
+    /// Draws the whole password, the cursor being where it starts
+    fn redraw_all(&mut self) -> String {
+        let width = self.display().width();
+        self.displayed_width = width;
+        self.displayed_cursor = self.display_cursor();
+        self.displayed_status = self.status();
+
+        let mut output = match self.status() {
+            Some(status) => self.theme.mask_style.paint(&status),
+            None => self.render(0, self.grapheme_count(), true),
+        };
+        output.push_str(self.wrap(width));
+        match self.capabilities.cursor_left {
+            Some(_) => output.push_str(&self.move_back(width, self.displayed_cursor)),
+            None if self.displayed_cursor < width => output.push_str(&self.return_to_cursor()),
+            None => {}
+        }
+        output
     }
 
-    pub fn into_password(self) -> String {
-        self.password.into_inner()
+    /// Redraws the whole line from its start, for terminals that cannot move the cursor left
+    fn redraw_line(&mut self) -> String {
+        let old_width = self.displayed_width;
+        let Some(cr) = self.capabilities.carriage_return.clone() else {
+            return String::new();
+        };
+
+        let mut output = cr;
+        output.push_str(&self.theme.prompt_style.paint(self.prompt_last_line()));
+        let width = self.display().width();
+        self.displayed_width = width;
+        self.displayed_cursor = self.display_cursor();
+        self.displayed_status = self.status();
+        match self.status() {
+            Some(ref status) => output.push_str(&self.theme.mask_style.paint(status)),
+            None => output.push_str(&self.render(0, self.grapheme_count(), true)),
+        }
+
+        let erased = match self.capabilities.clear_to_end {
+            Some(ref el) => {
+                output.push_str(el);
+                0
+            }
+            None => old_width.saturating_sub(width),
+        };
+        output.push_str(&" ".repeat(erased));
+        if self.displayed_cursor < width + erased {
+            output.push_str(&self.return_to_cursor());
+        }
+        output
+    }
+
+    /// Moves the cursor from anywhere on the line to where it belongs, by going back to the start
+    /// of the line and drawing again what comes before the cursor
+    fn return_to_cursor(&self) -> String {
+        let mut output = self
+            .capabilities
+            .carriage_return
+            .clone()
+            .unwrap_or_default();
+        output.push_str(&self.theme.prompt_style.paint(self.prompt_last_line()));
+        match self.status() {
+            Some(ref status) => output.push_str(&self.theme.mask_style.paint(status)),
+            None => output.push_str(&self.render(0, self.cursor, true)),
+        }
+        output
+    }
+
+    /// Redraws the screen after an edit that left the chars before `start` untouched
+    ///
+    /// Only the cells from `start` onwards are rewritten, and the cursor is then put back where it
+    /// belongs.
+    fn redraw_from(&mut self, start: usize) -> String {
+        if self.capabilities.cursor_left.is_none() {
+            return self.redraw_line();
+        }
+
+        let count = self.grapheme_count();
+        let start_cell = self.width(0, start);
+        let tail = self.render(start, count, true);
+        let width = self.width(0, count);
+        let cursor = self.width(0, self.cursor);
+        let old_width = std::mem::replace(&mut self.displayed_width, width);
+        let old_cursor = std::mem::replace(&mut self.displayed_cursor, cursor);
+        self.displayed_status = None;
+
+        if start == count && old_cursor == old_width && self.row(start_cell) == self.row(old_width)
+        {
+            // Nothing left to draw after the edit, erase the old cells one by one
+            let left = self.cursor_left();
+            return format!("{left} {left}").repeat(old_width - start_cell);
+        }
+
+        let mut output = self.move_back(old_cursor, start_cell);
+        output.push_str(&tail);
+        let erased = old_width.saturating_sub(width);
+        output.push_str(&" ".repeat(erased));
+        output.push_str(self.wrap(width + erased));
+        output.push_str(&self.move_back(width + erased, cursor));
+        output
+    }
+
+    /// Returns the row and column of the terminal the given cell of the password is drawn at,
+    /// rows counting from the one the last line of the prompt starts on
+    fn row_and_column(&self, cell: usize) -> (usize, usize) {
+        match self.columns() {
+            Some(columns) => {
+                let position = self.start_column + cell;
+                (position / columns, position % columns)
+            }
+            None => (0, self.start_column + cell),
+        }
+    }
+
+    fn row(&self, cell: usize) -> usize {
+        self.row_and_column(cell).0
+    }
+
+    /// Moves the cursor back from cell `from` to cell `to`
+    ///
+    /// Backspace cannot go up to the previous row, so the cursor is moved up and then to the right
+    /// column when the cells are on different rows.
+    fn move_back(&self, from: usize, to: usize) -> String {
+        let (from_row, _) = self.row_and_column(from);
+        let (to_row, to_column) = self.row_and_column(to);
+        if from_row > to_row {
+            format!("\x1B[{}A\x1B[{}G", from_row - to_row, to_column + 1)
+        } else {
+            self.cursor_left().repeat(from - to)
+        }
+    }
+
+    /// Returns the width of the terminal, if known and rows can be moved across
+    fn columns(&self) -> Option<usize> {
+        self.columns.filter(|_| self.capabilities.cursor_movement)
+    }
+
+    fn cursor_left(&self) -> &str {
+        self.capabilities.cursor_left.as_deref().unwrap_or("\x08")
+    }
+
+    /// Returns what moves the cursor to the next row after drawing up to `cell`, when that cell
+    /// starts a row
+    ///
+    /// Terminals leave the cursor on the last column after drawing it, until something else is
+    /// drawn, so it is moved explicitly to where the next cell goes.
+    fn wrap(&self, cell: usize) -> &'static str {
+        if cell > 0 && self.columns().is_some() && self.row_and_column(cell).1 == 0 {
+            "\r\n"
+        } else {
+            ""
+        }
     }
 }
 
+fn is_whitespace(grapheme: &str) -> bool {
+    grapheme.chars().next().is_some_and(char::is_whitespace)
+}
+
+fn is_alphanumeric(grapheme: &str) -> bool {
+    grapheme.chars().next().is_some_and(char::is_alphanumeric)
+}
+
 #[cfg(test)]
 mod tests {
     mod with_terminal_configuration {
-        use crate::config::PasswordFeedback;
+        use crate::config::{AfterSubmit, PasswordFeedback};
         use crate::feedback::FeedbackState;
+        use crate::terminfo::Capabilities;
+        use crate::theme::{Color, Style, Theme};
+        use std::time::Duration;
 
         #[test]
         fn feedback_state_mask_star() {
@@ -240,6 +981,418 @@ mod tests {
             assert_eq!(state.push_char('b'), "*");
             assert_eq!(state.into_password(), "ab");
         }
+
+        #[test]
+        fn feedback_state_move_and_insert() {
+            let mut state = FeedbackState::new(PasswordFeedback::Mask('*'), true);
+            state.push_char('a');
+            state.push_char('c');
+            assert_eq!(state.move_left(), "\x08");
+            assert_eq!(state.push_char('b'), "**\x08");
+            assert_eq!(state.move_home(), "\x08\x08");
+            assert_eq!(state.move_left(), "");
+            assert_eq!(state.move_end(), "***");
+            assert_eq!(state.move_right(), "");
+            assert_eq!(state.into_password(), "abc");
+        }
+
+        #[test]
+        fn feedback_state_insert_str() {
+            let mut state = FeedbackState::new(PasswordFeedback::Mask('*'), true);
+            state.push_char('a');
+            state.push_char('d');
+            state.move_left();
+            assert_eq!(state.insert_str("bc"), "***\x08");
+            assert_eq!(state.insert_str(""), "*\x08");
+            assert_eq!(state.into_password(), "abcd");
+        }
+
+        #[test]
+        fn feedback_state_flash_last() {
+            let timeout = Duration::from_secs(1);
+            let mut state = FeedbackState::new(PasswordFeedback::FlashLast('*', timeout), true);
+            assert_eq!(state.timeout(), None);
+            assert_eq!(state.push_char('a'), "a");
+            assert_eq!(state.timeout(), Some(timeout));
+            assert_eq!(state.push_char('b'), "\x08*b");
+            assert_eq!(state.time_out(), "\x08*");
+            assert_eq!(state.time_out(), "");
+            assert_eq!(state.timeout(), None);
+
+            state.move_left();
+            assert_eq!(state.push_char('c'), "c*\x08");
+            assert_eq!(state.display(), "*c*");
+            assert_eq!(state.time_out(), "\x08**\x08");
+            assert_eq!(state.into_password(), "acb");
+        }
+
+        #[test]
+        fn feedback_state_random_mask() {
+            let mut state = FeedbackState::new(PasswordFeedback::RandomMask('*'), true);
+            let widths: Vec<usize> = "ab cd".chars().map(|c| state.push_char(c).len()).collect();
+            assert!(widths.iter().all(|width| (1..=3).contains(width)));
+            assert_eq!(state.display(), "*".repeat(widths.iter().sum()));
+
+            assert_eq!(state.pop_char(), "\x08 \x08".repeat(widths[4]));
+            assert_eq!(state.clear_til_last_space(), "\x08 \x08".repeat(widths[3]));
+            assert_eq!(state.display_cursor(), widths[..3].iter().sum());
+
+            state.move_home();
+            let cells: usize = widths[..3].iter().sum();
+            assert_eq!(
+                state.clear(),
+                format!("{}{}", " ".repeat(cells), "\x08".repeat(cells))
+            );
+            assert_eq!(state.into_password(), "");
+        }
+
+        #[test]
+        fn feedback_state_indicator() {
+            let mut state = FeedbackState::new(PasswordFeedback::Indicator('*'), true);
+            assert_eq!(state.key_pressed(), "");
+            assert_eq!(state.push_char('a'), " * ");
+            assert_eq!(state.key_pressed(), "\x08\x08\x08  *");
+            assert_eq!(state.push_char('b'), "");
+            assert_eq!(state.key_pressed(), "\x08\x08\x08 * ");
+            assert_eq!(state.move_left(), "");
+            assert_eq!(state.key_pressed(), "\x08\x08\x08*  ");
+            assert_eq!(state.pop_char(), "");
+            assert_eq!(state.display(), "*  ");
+            assert_eq!(state.display_cursor(), 3);
+            state.key_pressed();
+            assert_eq!(state.clear(), "\x08\x08\x08   \x08\x08\x08");
+            assert_eq!(state.display(), "");
+            assert_eq!(state.into_password(), "");
+        }
+
+        #[test]
+        fn feedback_state_counter() {
+            let mut state = FeedbackState::new(PasswordFeedback::Counter(None), true);
+            assert_eq!(state.push_char('a'), "(1 character)");
+            assert_eq!(
+                state.push_char('b'),
+                format!("{}(2 characters)", "\x08".repeat(13))
+            );
+            assert_eq!(state.display_cursor(), 14);
+            assert_eq!(
+                state.pop_char(),
+                format!("{}(1 character) \x08", "\x08".repeat(14))
+            );
+            assert_eq!(
+                state.clear(),
+                format!(
+                    "{}{}{}",
+                    "\x08".repeat(13),
+                    " ".repeat(13),
+                    "\x08".repeat(13)
+                )
+            );
+            assert_eq!(state.display(), "");
+        }
+
+        #[test]
+        fn feedback_state_counter_max() {
+            let mut state = FeedbackState::new(PasswordFeedback::Counter(Some(10)), true);
+            for c in "abcdefgh".chars() {
+                state.push_char(c);
+            }
+            assert_eq!(state.display(), "(8 characters)");
+            state.push_char('i');
+            assert_eq!(state.display(), "(9 characters, max 10)");
+            state.push_char('j');
+            state.push_char('k');
+            assert_eq!(state.display(), "(11 characters, max 10)");
+            assert_eq!(state.into_password(), "abcdefghijk");
+        }
+
+        #[test]
+        fn feedback_state_combining_chars() {
+            let mut state = FeedbackState::new(PasswordFeedback::Mask('*'), true);
+            assert_eq!(state.push_char('e'), "*");
+            assert_eq!(state.push_char('\u{301}'), "\x08*");
+            assert_eq!(state.display(), "*");
+            assert_eq!(state.push_char('x'), "*");
+            assert_eq!(state.move_left(), "\x08");
+            assert_eq!(state.move_left(), "\x08");
+            assert_eq!(state.delete_char(), "* \x08\x08");
+            assert_eq!(state.into_password(), "x");
+        }
+
+        #[test]
+        fn feedback_state_emoji_sequence() {
+            let mut state = FeedbackState::new(PasswordFeedback::Mask('*'), true);
+            for c in "a👩\u{200D}💻".chars() {
+                state.push_char(c);
+            }
+            assert_eq!(state.display(), "**");
+            assert_eq!(state.pop_char(), "\x08 \x08");
+            assert_eq!(state.into_password(), "a");
+        }
+
+        #[test]
+        fn feedback_state_wide_chars() {
+            let mut state = FeedbackState::new(PasswordFeedback::PartialMask('*', 2), true);
+            assert_eq!(state.push_char('密'), "密");
+            assert_eq!(state.push_char('码'), "码");
+            assert_eq!(state.push_char('!'), "*");
+            assert_eq!(state.display_cursor(), 5);
+            assert_eq!(state.move_left(), "\x08");
+            assert_eq!(state.move_left(), "\x08\x08");
+            // The chars after the deleted one move into the plaintext part
+            assert_eq!(state.pop_char(), "\x08\x08码!  \x08\x08\x08\x08\x08");
+            assert_eq!(state.move_end(), "码!");
+            assert_eq!(state.pop_char(), "\x08 \x08");
+            assert_eq!(state.clear(), "\x08 \x08\x08 \x08");
+            assert_eq!(state.into_password(), "");
+        }
+
+        #[test]
+        fn feedback_state_wrapped_rows() {
+            let mut state = FeedbackState::new(PasswordFeedback::Mask('*'), true);
+            state.set_prompt("> ".to_string());
+            state.set_terminal_width(4);
+            assert_eq!(state.push_char('a'), "*");
+            assert_eq!(state.push_char('b'), "*\r\n");
+            assert_eq!(state.push_char('c'), "*");
+            assert_eq!(state.move_home(), "\x1B[1A\x1B[3G");
+            assert_eq!(state.move_right(), "*");
+            assert_eq!(state.move_right(), "*\r\n");
+            assert_eq!(state.pop_char(), "\x1B[1A\x1B[4G* \x1B[1A\x1B[4G");
+            assert_eq!(state.pop_char(), "\x08* \r\n\x1B[1A\x1B[3G");
+            assert_eq!(state.move_end(), "*");
+            assert_eq!(state.finish(), "\n");
+            assert_eq!(state.into_password(), "c");
+        }
+
+        #[test]
+        fn feedback_state_repaint() {
+            let mut state = FeedbackState::new(PasswordFeedback::Mask('*'), true);
+            state.set_prompt("Password\n> ".to_string());
+            state.set_terminal_width(4);
+            state.push_char('a');
+            state.push_char('b');
+            state.push_char('c');
+            state.move_left();
+            assert_eq!(state.resize(3), "\x1B[1A\r\x1B[J> ***\x08");
+            assert_eq!(state.move_right(), "*");
+            assert_eq!(state.clear_screen(), "\x1B[H\x1B[2JPassword\n> ***");
+            assert_eq!(state.pop_char(), "\x08 \x08");
+            assert_eq!(state.resize(4), "\x1B[1A\r\x1B[J> **\r\n");
+        }
+
+        #[test]
+        fn feedback_state_suspend() {
+            let mut state = FeedbackState::new(PasswordFeedback::Mask('*'), true);
+            state.set_prompt("Password\n> ".to_string());
+            state.set_terminal_width(4);
+            state.push_char('a');
+            state.push_char('b');
+            state.push_char('c');
+            state.move_left();
+            assert_eq!(state.suspend(), "*\n");
+            assert_eq!(state.resume(Some(3)), "Password\n> ***\x08");
+            assert_eq!(state.move_right(), "*");
+        }
+
+        #[test]
+        fn feedback_state_after_submit_erase() {
+            let mut state = FeedbackState::new(PasswordFeedback::PartialMask('*', 1), true);
+            state.set_prompt("> ".to_string());
+            state.set_after_submit(AfterSubmit::Erase);
+            state.push_char('a');
+            state.push_char('b');
+            assert_eq!(state.finish(), "\r\x1B[K");
+
+            state.set_terminal_width(3);
+            assert_eq!(state.finish(), "\x1B[1A\r\x1B[J");
+            assert_eq!(state.into_password(), "ab");
+        }
+
+        #[test]
+        fn feedback_state_console_capabilities() {
+            let mut state = FeedbackState::new(PasswordFeedback::Mask('*'), true);
+            state.set_capabilities(Capabilities::console());
+            state.set_prompt("> ".to_string());
+            state.set_terminal_width(3);
+            assert_eq!(state.push_char('a'), "*");
+            assert_eq!(state.push_char('b'), "*");
+            assert_eq!(state.move_left(), "\x08");
+            assert_eq!(state.pop_char(), "\x08* \x08\x08");
+            assert_eq!(state.resize(4), "");
+            assert_eq!(state.clear_screen(), "\n> *\x08");
+            state.set_after_submit(AfterSubmit::Erase);
+            assert_eq!(state.finish(), "\r   \r");
+        }
+
+        #[test]
+        fn feedback_state_carriage_return_capabilities() {
+            let mut state = FeedbackState::new(PasswordFeedback::Mask('*'), true);
+            state.set_capabilities(Capabilities {
+                cursor_left: None,
+                ..Capabilities::console()
+            });
+            state.set_prompt("> ".to_string());
+            assert_eq!(state.push_char('a'), "\r> *");
+            assert_eq!(state.push_char('b'), "\r> **");
+            assert_eq!(state.move_left(), "\r> **\r> *");
+            assert_eq!(state.push_char('c'), "\r> ***\r> **");
+            assert_eq!(state.move_end(), "*");
+            assert_eq!(state.pop_char(), "\r> ** \r> **");
+            assert_eq!(state.clear(), "\r>   \r> ");
+            assert_eq!(state.finish(), "\n");
+        }
+
+        #[test]
+        fn feedback_state_after_submit_replace() {
+            let after_submit = AfterSubmit::Replace {
+                submitted: "[accepted]".to_string(),
+                cancelled: "[cancelled]".to_string(),
+            };
+            let mut state = FeedbackState::new(PasswordFeedback::Mask('*'), true);
+            state.set_prompt("Password\n> ".to_string());
+            state.set_after_submit(after_submit.clone());
+            state.push_char('a');
+            assert_eq!(state.finish(), "\r\x1B[K> [accepted]\n");
+
+            let mut state = FeedbackState::new(PasswordFeedback::Mask('*'), true);
+            state.set_prompt("> ".to_string());
+            state.set_terminal_width(80);
+            state.set_after_submit(after_submit);
+            state.push_char('a');
+            assert_eq!(state.abort(), "\r\x1B[J> [cancelled]\n");
+        }
+
+        #[test]
+        fn feedback_state_theme() {
+            let theme = Theme::new()
+                .prompt_style(Style::new().bold())
+                .mask_style(Style::new().fg(Color::Grey))
+                .hint("hint")
+                .hint_style(Style::new().dim());
+            let mut state = FeedbackState::new(PasswordFeedback::PartialMask('*', 1), true);
+            state.set_prompt("> ".to_string());
+            state.set_theme(theme);
+            assert_eq!(state.prompt_output(), "\x1B[1m> \x1B[0m");
+
+            state.set_terminal_width(10);
+            assert_eq!(
+                state.prompt_output(),
+                "\x1B[1m> \x1B[0m\x1B[7G\x1B[2mhint\x1B[0m\x1B[3G"
+            );
+            assert_eq!(state.push_char('a'), "a");
+            assert_eq!(state.push_char('b'), "\x1B[90m*\x1B[0m");
+            assert_eq!(state.push_char('c'), "\x1B[90m*\x1B[0m");
+            assert_eq!(state.move_home(), "\x08\x08\x08");
+            assert_eq!(state.delete_char(), "b\x1B[90m*\x1B[0m \x08\x08\x08");
+            assert_eq!(state.display(), "b*");
+        }
+
+        #[test]
+        fn feedback_state_partial_mask_insert_shifts_plaintext() {
+            let mut state = FeedbackState::new(PasswordFeedback::PartialMask('*', 2), true);
+            state.push_char('a');
+            state.push_char('b');
+            state.push_char('c');
+            state.move_home();
+            assert_eq!(state.push_char('x'), "xa**\x08\x08\x08");
+            assert_eq!(state.into_password(), "xabc");
+        }
+
+        #[test]
+        fn feedback_state_delete_char() {
+            let mut state = FeedbackState::new(PasswordFeedback::Mask('*'), true);
+            state.push_char('a');
+            state.push_char('b');
+            state.push_char('c');
+            assert!(state.delete_char().is_empty());
+            state.move_home();
+            assert_eq!(state.delete_char(), "** \x08\x08\x08");
+            assert_eq!(state.into_password(), "bc");
+        }
+
+        #[test]
+        fn feedback_state_pop_char_in_the_middle() {
+            let mut state = FeedbackState::new(PasswordFeedback::Mask('*'), true);
+            state.push_char('a');
+            state.push_char('b');
+            state.push_char('c');
+            state.move_left();
+            assert_eq!(state.pop_char(), "\x08* \x08\x08");
+            assert_eq!(state.into_password(), "ac");
+        }
+
+        #[test]
+        fn feedback_state_kill_to_end() {
+            let mut state = FeedbackState::new(PasswordFeedback::Mask('*'), true);
+            state.push_char('a');
+            state.push_char('b');
+            state.push_char('c');
+            state.move_left();
+            state.move_left();
+            assert_eq!(state.kill_to_end(), "  \x08\x08");
+            assert_eq!(state.into_password(), "a");
+        }
+
+        #[test]
+        fn feedback_state_clear_in_the_middle() {
+            let mut state = FeedbackState::new(PasswordFeedback::Mask('*'), true);
+            state.push_char('a');
+            state.push_char('b');
+            state.move_left();
+            assert_eq!(state.clear(), "\x08  \x08\x08");
+            assert!(state.is_empty());
+        }
+
+        #[test]
+        fn feedback_state_toggle_reveal() {
+            let mut state = FeedbackState::new(PasswordFeedback::Mask('*'), true);
+            state.push_char('a');
+            state.push_char('b');
+            state.move_left();
+            assert_eq!(state.toggle_reveal(), "\x08ab\x08");
+            assert_eq!(state.push_char('c'), "cb\x08");
+            assert_eq!(state.toggle_reveal(), "\x08\x08***\x08");
+            assert_eq!(state.into_password(), "acb");
+        }
+
+        #[test]
+        fn feedback_state_toggle_reveal_hide() {
+            let mut state = FeedbackState::new(PasswordFeedback::Hide, true);
+            state.push_char('a');
+            state.push_char('b');
+            assert_eq!(state.toggle_reveal(), "ab");
+            assert_eq!(state.move_left(), "\x08");
+            assert_eq!(state.toggle_reveal(), "\x08  \x08\x08");
+            assert!(state.push_char('c').is_empty());
+            assert_eq!(state.into_password(), "acb");
+        }
+
+        #[test]
+        fn feedback_state_word_movement() {
+            let mut state = FeedbackState::new(PasswordFeedback::Mask('*'), true);
+            for c in "ab-cd ef".chars() {
+                state.push_char(c);
+            }
+            assert_eq!(state.move_word_left(), "\x08\x08");
+            assert_eq!(state.move_word_left(), "\x08\x08\x08");
+            assert_eq!(state.move_word_left(), "\x08\x08\x08");
+            assert_eq!(state.move_word_right(), "**");
+            assert_eq!(state.move_word_right(), "***");
+            assert_eq!(state.into_password(), "ab-cd ef");
+        }
+
+        #[test]
+        fn feedback_state_word_deletion() {
+            let mut state = FeedbackState::new(PasswordFeedback::Mask('*'), true);
+            for c in "ab cd-ef".chars() {
+                state.push_char(c);
+            }
+            assert_eq!(state.delete_word_backward(), "\x08 \x08\x08 \x08");
+            assert_eq!(state.clear_til_last_space(), "\x08 \x08\x08 \x08\x08 \x08");
+            state.move_home();
+            assert_eq!(state.delete_word_forward(), "*  \x08\x08\x08");
+            assert_eq!(state.into_password(), " ");
+        }
     }
 
     mod without_terminal_configuration {
@@ -0,0 +1,602 @@
+use crate::config::Config;
+use crate::utf8::read_char;
+use crate::{RawModeInput, RawPasswordInput};
+use rtoolbox::safe_string::SafeString;
+use std::io::{self, Read};
+use std::time::Duration;
+
+/// A key pressed by the user, as decoded from the input.
+///
+/// Control characters are reported as `Ctrl` with the matching lowercase letter or symbol, e.g.
+/// `Ctrl('w')` for `\x17`, except for those with a dedicated variant such as `Enter` and `Tab`.
+/// Note that `\x08` is reported as `Ctrl('h')`, since it is what Ctrl-H sends, while the
+/// Backspace key of most terminals sends `\x7F` and is reported as `Backspace`.
+#[derive(Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Key {
+    /// A printable character
+    Char(char),
+    /// A control character, e.g. `Ctrl('u')`
+    Ctrl(char),
+    /// A key pressed while holding Alt, e.g. `Alt(Box::new(Key::Char('b')))`
+    Alt(Box<Key>),
+    Enter,
+    Tab,
+    Backspace,
+    Delete,
+    Esc,
+    Up,
+    Down,
+    Left,
+    Right,
+    CtrlLeft,
+    CtrlRight,
+    Home,
+    End,
+    /// Text pasted at once, when the terminal has bracketed paste mode enabled
+    Paste(String),
+    /// An escape sequence or character that could not be decoded
+    Unknown(Vec<u8>),
+}
+
+impl std::fmt::Debug for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Key::Char(c) => f.debug_tuple("Char").field(c).finish(),
+            Key::Ctrl(c) => f.debug_tuple("Ctrl").field(c).finish(),
+            Key::Alt(key) => f.debug_tuple("Alt").field(key).finish(),
+            Key::Enter => f.write_str("Enter"),
+            Key::Tab => f.write_str("Tab"),
+            Key::Backspace => f.write_str("Backspace"),
+            Key::Delete => f.write_str("Delete"),
+            Key::Esc => f.write_str("Esc"),
+            Key::Up => f.write_str("Up"),
+            Key::Down => f.write_str("Down"),
+            Key::Left => f.write_str("Left"),
+            Key::Right => f.write_str("Right"),
+            Key::CtrlLeft => f.write_str("CtrlLeft"),
+            Key::CtrlRight => f.write_str("CtrlRight"),
+            Key::Home => f.write_str("Home"),
+            Key::End => f.write_str("End"),
+            // Pasted text is most likely a password, which must not end up in logs
+            Key::Paste(text) => write!(f, "Paste(<{} chars>)", text.chars().count()),
+            Key::Unknown(bytes) => f.debug_tuple("Unknown").field(bytes).finish(),
+        }
+    }
+}
+
+impl Key {
+    /// Returns the key for the given char, as if it was typed on its own
+    pub fn from_char(c: char) -> Key {
+        match c {
+            '\r' | '\n' => Key::Enter,
+            '\t' => Key::Tab,
+            '\x1B' => Key::Esc,
+            '\x7F' => Key::Backspace,
+            '\x00'..='\x1F' => Key::Ctrl(((c as u8) | 0x40).to_ascii_lowercase() as char),
+            c if c.is_control() => Key::Unknown(c.to_string().into_bytes()),
+            c => Key::Char(c),
+        }
+    }
+
+    /// Returns the char this key produces when it is inserted as text, if any
+    pub fn as_char(&self) -> Option<char> {
+        match self {
+            Key::Char(c) => Some(*c),
+            Key::Ctrl(c) => Some((c.to_ascii_uppercase() as u8 & 0x1F) as char),
+            Key::Enter => Some('\n'),
+            Key::Tab => Some('\t'),
+            Key::Backspace => Some('\x7F'),
+            Key::Esc => Some('\x1B'),
+            _ => None,
+        }
+    }
+}
+
+/// Turns raw input into the keys typed by the user.
+///
+/// # Examples
+///
+/// ## Decoding keys from `impl Read`
+/// ```
+/// use std::io::Cursor;
+/// use rpassword::{Key, KeyReader};
+///
+/// let keys = KeyReader::new(Cursor::new("a\x1B[D\x7F"))
+///     .collect::<std::io::Result<Vec<Key>>>()
+///     .unwrap();
+/// assert_eq!(keys, vec![Key::Char('a'), Key::Left, Key::Backspace]);
+/// ```
+///
+/// ## Decoding keys from the terminal
+/// ```no_run
+/// use rpassword::{ConfigBuilder, Key, KeyReader};
+///
+/// // The terminal stays in raw mode until the reader is dropped
+/// let mut reader = KeyReader::from_config(ConfigBuilder::new().build()).unwrap();
+/// println!("Press any key to continue");
+/// if let Some(Key::Ctrl('c')) = reader.read_key().unwrap() {
+///     println!("Bye");
+/// }
+/// ```
+pub struct KeyReader {
+    source: Box<dyn CharSource>,
+    escape_timeout: Duration,
+}
+
+impl KeyReader {
+    /// Decodes keys from `impl Read`.
+    ///
+    /// Since there is no way to tell whether more input is coming, Esc followed by another key is
+    /// always decoded as an Alt-key combination.
+    pub fn new(reader: impl Read + 'static) -> KeyReader {
+        KeyReader {
+            source: Box::new(ReaderChars(reader)),
+            escape_timeout: DEFAULT_ESCAPE_TIMEOUT,
+        }
+    }
+
+    /// Decodes keys from the input of the config, which is the terminal by default.
+    ///
+    /// If the input is a terminal, it is put in raw mode until the returned reader is dropped.
+    /// When the program is stopped, e.g. with `kill -TSTP`, the terminal is restored meanwhile.
+    pub fn from_config(config: Config) -> io::Result<KeyReader> {
+        let escape_timeout = config.options.escape_timeout;
+        let mut input = RawModeInput::new(config)?;
+        if input.needs_terminal_configuration() {
+            input.apply_terminal_configuration()?;
+        }
+
+        Ok(KeyReader {
+            source: Box::new(TerminalChars(input)),
+            escape_timeout,
+        })
+    }
+
+    /// Reads the next key, returning `None` at the end of the input.
+    pub fn read_key(&mut self) -> io::Result<Option<Key>> {
+        read_key(self.source.as_mut(), self.escape_timeout)
+    }
+}
+
+impl Iterator for KeyReader {
+    type Item = io::Result<Key>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_key().transpose()
+    }
+}
+
+/// Reads chars from `impl Read`, which cannot be polled
+struct ReaderChars<R>(R);
+
+impl<R: Read> CharSource for ReaderChars<R> {
+    fn read_char(&mut self) -> io::Result<char> {
+        read_char(&mut self.0)
+    }
+
+    fn poll_char(&mut self, _timeout: Duration) -> io::Result<bool> {
+        Ok(true)
+    }
+}
+
+/// Reads chars from the terminal, stopping the program when asked to while waiting for a key
+struct TerminalChars(RawModeInput);
+
+impl CharSource for TerminalChars {
+    fn read_char(&mut self) -> io::Result<char> {
+        // Signals are only noticed while waiting, reads being restarted after them
+        while !self.0.wait_for_key(None)? {
+            if self.0.stop_requested() {
+                self.0.stop()?;
+            }
+            self.0.continued()?;
+        }
+        self.0.read_char()
+    }
+
+    fn poll_char(&mut self, timeout: Duration) -> io::Result<bool> {
+        self.0.poll_char(timeout)
+    }
+}
+
+/// How long to wait for the rest of an escape sequence before considering Esc was pressed alone
+pub(crate) const DEFAULT_ESCAPE_TIMEOUT: Duration = Duration::from_millis(50);
+
+const ESC: char = '\x1B';
+/// Marks the end of pasted text in bracketed paste mode
+const PASTE_END: &str = "\x1B[201~";
+const BEL: char = '\x07';
+/// 8-bit C1 controls, as they appear once decoded from UTF-8
+const SS3: char = '\u{8F}';
+const DCS: char = '\u{90}';
+const SOS: char = '\u{98}';
+const CSI: char = '\u{9B}';
+const ST: char = '\u{9C}';
+const OSC: char = '\u{9D}';
+const PM: char = '\u{9E}';
+const APC: char = '\u{9F}';
+
+/// Where the key decoder reads chars from
+pub(crate) trait CharSource {
+    fn read_char(&mut self) -> io::Result<char>;
+
+    /// Waits up to `timeout` for input, returning whether a char can be read without blocking
+    fn poll_char(&mut self, timeout: Duration) -> io::Result<bool>;
+}
+
+/// Reads a key, turning the end of the input into `None`
+///
+/// Escape sequences are parsed following ECMA-48. Sequences that do not map to a key, such as
+/// replies to terminal queries, are returned as `Key::Unknown` so that they never end up in the
+/// password. If no char follows Esc within `escape_timeout`, Esc was pressed on its own.
+pub(crate) fn read_key(
+    source: &mut (impl CharSource + ?Sized),
+    escape_timeout: Duration,
+) -> io::Result<Option<Key>> {
+    let Some(c) = read_char_until_eof(source)? else {
+        return Ok(None);
+    };
+
+    let mut sequence = Sequence {
+        source,
+        timeout: escape_timeout,
+        text: c.to_string(),
+    };
+    let key = match c {
+        ESC => sequence.escape()?,
+        CSI => sequence.control_sequence()?,
+        SS3 => sequence.single_shift()?,
+        DCS | SOS | OSC | PM | APC => sequence.control_string()?,
+        c => Key::from_char(c),
+    };
+
+    Ok(Some(key))
+}
+
+fn read_char_until_eof(source: &mut (impl CharSource + ?Sized)) -> io::Result<Option<char>> {
+    match source.read_char() {
+        Ok(c) => Ok(Some(c)),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// An escape sequence being parsed, along with the chars read so far
+struct Sequence<'a, S: CharSource + ?Sized> {
+    source: &'a mut S,
+    timeout: Duration,
+    text: String,
+}
+
+impl<S: CharSource + ?Sized> Sequence<'_, S> {
+    /// Reads the next char of the sequence, or `None` if it does not come in time
+    fn next(&mut self) -> io::Result<Option<char>> {
+        if !self.source.poll_char(self.timeout)? {
+            return Ok(None);
+        }
+        let c = read_char_until_eof(self.source)?;
+        if let Some(c) = c {
+            self.text.push(c);
+        }
+        Ok(c)
+    }
+
+    fn unknown(&mut self) -> Key {
+        Key::Unknown(std::mem::take(&mut self.text).into_bytes())
+    }
+
+    /// Parses what follows ESC
+    fn escape(&mut self) -> io::Result<Key> {
+        let Some(c) = self.next()? else {
+            return Ok(Key::Esc);
+        };
+
+        match c {
+            '[' => self.control_sequence(),
+            'O' => self.single_shift(),
+            // Alt-P and the like, unless the rest of a string comes along as for a sequence
+            'P' | 'X' | ']' | '^' | '_' if self.source.poll_char(self.timeout)? => {
+                self.control_string()
+            }
+            // Some terminals send Alt-Up as ESC ESC [ A
+            ESC => Ok(Key::Alt(Box::new(self.escape()?))),
+            // nF sequences such as ESC ( B: intermediate bytes, then a final byte
+            '\x20'..='\x2F' => loop {
+                match self.next()? {
+                    Some('\x20'..='\x2F') => {}
+                    _ => return Ok(self.unknown()),
+                }
+            },
+            c => Ok(Key::Alt(Box::new(Key::from_char(c)))),
+        }
+    }
+
+    /// Parses a CSI sequence: parameter bytes, intermediate bytes, then a final byte
+    fn control_sequence(&mut self) -> io::Result<Key> {
+        let mut parameters = String::new();
+        let mut intermediates = false;
+        let final_byte = loop {
+            match self.next()? {
+                Some(c @ '\x30'..='\x3F') if !intermediates => parameters.push(c),
+                Some('\x20'..='\x2F') => intermediates = true,
+                Some(c @ '\x40'..='\x7E') => break c,
+                _ => return Ok(self.unknown()),
+            }
+        };
+        if intermediates {
+            return Ok(self.unknown());
+        }
+
+        let key = match (final_byte, parameters.split(';').next()) {
+            ('A', _) => Key::Up,
+            ('B', _) => Key::Down,
+            ('C', _) => Key::Right,
+            ('D', _) => Key::Left,
+            ('H', _) | ('~', Some("1" | "7")) => Key::Home,
+            ('F', _) | ('~', Some("4" | "8")) => Key::End,
+            ('~', Some("3")) => Key::Delete,
+            ('~', Some("200")) => return self.paste(),
+            _ => return Ok(self.unknown()),
+        };
+
+        // Modifiers are sent as a second parameter, e.g. ESC [1;5C for Ctrl-Right
+        let modifiers = parameters
+            .split(';')
+            .nth(1)
+            .and_then(|modifiers| modifiers.parse::<u8>().ok())
+            .map_or(0, |modifiers| modifiers.saturating_sub(1));
+        Ok(match key {
+            Key::Left if modifiers & 4 != 0 => Key::CtrlLeft,
+            Key::Right if modifiers & 4 != 0 => Key::CtrlRight,
+            key if modifiers & 2 != 0 => Key::Alt(Box::new(key)),
+            key => key,
+        })
+    }
+
+    /// Reads pasted text, up to the end of the paste
+    ///
+    /// The terminal sends the pasted text at once, so there is no timeout between chars.
+    fn paste(&mut self) -> io::Result<Key> {
+        let mut text = SafeString::new();
+        while let Some(c) = read_char_until_eof(self.source)? {
+            text.push(c);
+            if text.ends_with(PASTE_END) {
+                let length = text.len() - PASTE_END.len();
+                text.truncate(length);
+                break;
+            }
+        }
+        self.text.clear();
+        Ok(Key::Paste(text.into_inner()))
+    }
+
+    /// Parses an SS3 sequence, which is a single final byte
+    fn single_shift(&mut self) -> io::Result<Key> {
+        Ok(match self.next()? {
+            Some('A') => Key::Up,
+            Some('B') => Key::Down,
+            Some('C') => Key::Right,
+            Some('D') => Key::Left,
+            Some('H') => Key::Home,
+            Some('F') => Key::End,
+            Some('M') => Key::Enter,
+            _ => self.unknown(),
+        })
+    }
+
+    /// Skips an OSC, DCS, SOS, PM or APC string, up to the string terminator (ESC \\) or BEL
+    fn control_string(&mut self) -> io::Result<Key> {
+        loop {
+            match self.next()? {
+                Some(ESC) => {
+                    self.next()?;
+                    return Ok(self.unknown());
+                }
+                Some(BEL | ST) | None => return Ok(self.unknown()),
+                Some(_) => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::key::{CharSource, DEFAULT_ESCAPE_TIMEOUT, Key, KeyReader, read_key};
+    use std::collections::VecDeque;
+    use std::io::{self, Cursor};
+    use std::time::Duration;
+
+    /// Chars typed by the user, `None` standing for a pause longer than the escape timeout
+    struct Typed(VecDeque<Option<char>>);
+
+    impl Typed {
+        fn new(chunks: &[&str]) -> Typed {
+            let mut typed = VecDeque::new();
+            for chunk in chunks {
+                typed.extend(chunk.chars().map(Some));
+                typed.push_back(None);
+            }
+            Typed(typed)
+        }
+
+        fn keys(mut self) -> Vec<Key> {
+            let mut keys = Vec::new();
+            while let Some(key) = read_key(&mut self, DEFAULT_ESCAPE_TIMEOUT).unwrap() {
+                keys.push(key);
+            }
+            keys
+        }
+    }
+
+    impl CharSource for Typed {
+        fn read_char(&mut self) -> io::Result<char> {
+            while let Some(c) = self.0.pop_front() {
+                if let Some(c) = c {
+                    return Ok(c);
+                }
+            }
+            Err(io::ErrorKind::UnexpectedEof.into())
+        }
+
+        fn poll_char(&mut self, _timeout: Duration) -> io::Result<bool> {
+            match self.0.front() {
+                Some(Some(_)) => Ok(true),
+                Some(None) => {
+                    self.0.pop_front();
+                    Ok(false)
+                }
+                None => Ok(false),
+            }
+        }
+    }
+
+    fn unknown(sequence: &str) -> Key {
+        Key::Unknown(sequence.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn read_key_bare_esc() {
+        assert_eq!(
+            Typed::new(&["\x1B", "a"]).keys(),
+            vec![Key::Esc, Key::Char('a')]
+        );
+        assert_eq!(Typed::new(&["\x1B"]).keys(), vec![Key::Esc]);
+    }
+
+    #[test]
+    fn read_key_alt() {
+        assert_eq!(
+            Typed::new(&["\x1Bb\x1B\x7F\x1B\x1B[A"]).keys(),
+            vec![
+                Key::Alt(Box::new(Key::Char('b'))),
+                Key::Alt(Box::new(Key::Backspace)),
+                Key::Alt(Box::new(Key::Up)),
+            ]
+        );
+    }
+
+    #[test]
+    fn read_key_control_sequences() {
+        assert_eq!(
+            Typed::new(&["\x1B[D\x1B[1;5C\x1B[1;3H\x1B[3~\x1BOF\u{9B}A\x1B[?1;2c\x1B[Z"]).keys(),
+            vec![
+                Key::Left,
+                Key::CtrlRight,
+                Key::Alt(Box::new(Key::Home)),
+                Key::Delete,
+                Key::End,
+                Key::Up,
+                unknown("\x1B[?1;2c"),
+                unknown("\x1B[Z"),
+            ]
+        );
+    }
+
+    #[test]
+    fn read_key_paste() {
+        assert_eq!(
+            Typed::new(&["a\x1B[200~my\x1B[Dpass\x1B[201~b", "\x1B[200~word"]).keys(),
+            vec![
+                Key::Char('a'),
+                Key::Paste("my\x1B[Dpass".to_string()),
+                Key::Char('b'),
+                Key::Paste("word".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn key_debug_redacts_pastes() {
+        assert_eq!(
+            format!("{:?}", Key::Paste("hunter2".to_string())),
+            "Paste(<7 chars>)"
+        );
+        assert_eq!(
+            format!("{:?}", Key::Alt(Box::new(Key::Char('b')))),
+            "Alt(Char('b'))"
+        );
+    }
+
+    #[test]
+    fn key_reader() {
+        let keys = KeyReader::new(Cursor::new("\x1Bb\x03\u{E9}\x1B[1;5D"))
+            .collect::<io::Result<Vec<Key>>>()
+            .unwrap();
+        assert_eq!(
+            keys,
+            vec![
+                Key::Alt(Box::new(Key::Char('b'))),
+                Key::Ctrl('c'),
+                Key::Char('\u{E9}'),
+                Key::CtrlLeft,
+            ]
+        );
+    }
+
+    #[test]
+    fn read_key_control_strings() {
+        assert_eq!(
+            Typed::new(&["\x1B]11;rgb:0/0/0\x07a\x1BP1$r0m\x1B\\b\x1B_Gi=1;OK\x1B\\c"]).keys(),
+            vec![
+                unknown("\x1B]11;rgb:0/0/0\x07"),
+                Key::Char('a'),
+                unknown("\x1BP1$r0m\x1B\\"),
+                Key::Char('b'),
+                unknown("\x1B_Gi=1;OK\x1B\\"),
+                Key::Char('c'),
+            ]
+        );
+    }
+
+    #[test]
+    fn read_key_alt_starting_control_strings() {
+        assert_eq!(
+            Typed::new(&["\x1BP", "a\x1B]", "\x1B_"]).keys(),
+            vec![
+                Key::Alt(Box::new(Key::Char('P'))),
+                Key::Char('a'),
+                Key::Alt(Box::new(Key::Char(']'))),
+                Key::Alt(Box::new(Key::Char('_'))),
+            ]
+        );
+    }
+
+    #[test]
+    fn read_key_interrupted_sequences() {
+        assert_eq!(
+            Typed::new(&["\x1B[1;", "a", "\x1B(B", "\x1B]0;title", "b"]).keys(),
+            vec![
+                unknown("\x1B[1;"),
+                Key::Char('a'),
+                unknown("\x1B(B"),
+                unknown("\x1B]0;title"),
+                Key::Char('b'),
+            ]
+        );
+    }
+
+    #[test]
+    fn key_from_char() {
+        assert_eq!(Key::from_char('a'), Key::Char('a'));
+        assert_eq!(Key::from_char('🚲'), Key::Char('🚲'));
+        assert_eq!(Key::from_char('\r'), Key::Enter);
+        assert_eq!(Key::from_char('\n'), Key::Enter);
+        assert_eq!(Key::from_char('\t'), Key::Tab);
+        assert_eq!(Key::from_char('\x7F'), Key::Backspace);
+        assert_eq!(Key::from_char('\x08'), Key::Ctrl('h'));
+        assert_eq!(Key::from_char('\x17'), Key::Ctrl('w'));
+        assert_eq!(Key::from_char('\x00'), Key::Ctrl('@'));
+        assert_eq!(Key::from_char('\x1F'), Key::Ctrl('_'));
+        assert_eq!(Key::from_char('\u{85}'), Key::Unknown(vec![0xC2, 0x85]));
+    }
+
+    #[test]
+    fn key_as_char() {
+        assert_eq!(Key::Char('a').as_char(), Some('a'));
+        assert_eq!(Key::Ctrl('w').as_char(), Some('\x17'));
+        assert_eq!(Key::Ctrl('@').as_char(), Some('\x00'));
+        assert_eq!(Key::Tab.as_char(), Some('\t'));
+        assert_eq!(Key::Left.as_char(), None);
+    }
+}
@@ -0,0 +1,305 @@
+use crate::key::Key;
+use std::collections::{HashMap, HashSet};
+
+/// What the password editor does when a key is pressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Action {
+    /// Accepts the password as typed so far
+    Submit,
+    /// Aborts the prompt, like Ctrl-C does by default
+    Cancel,
+    /// Ends the input when the password is empty, deletes the char under the cursor otherwise
+    EndOfFile,
+    /// Deletes the char before the cursor
+    EraseChar,
+    /// Deletes the char under the cursor
+    EraseCharForward,
+    /// Deletes the whitespace-delimited word before the cursor
+    EraseWord,
+    /// Deletes the alphanumeric word before the cursor
+    EraseAlphanumericWord,
+    /// Deletes the alphanumeric word after the cursor
+    EraseWordForward,
+    /// Deletes the whole password
+    EraseLine,
+    /// Deletes from the cursor to the end of the password
+    EraseToEnd,
+    MoveLeft,
+    MoveRight,
+    MoveHome,
+    MoveEnd,
+    MoveWordLeft,
+    MoveWordRight,
+    /// Switches between the configured feedback and showing the password in plaintext
+    Reveal,
+    /// Clears the screen, then draws the prompt and the password again
+    ClearScreen,
+    /// Suspends the program, like Ctrl-Z does by default, drawing the prompt again once resumed
+    Suspend,
+    /// Inserts the next key as text, even if it is bound to an action
+    LiteralNext,
+    /// Does nothing
+    Ignore,
+    /// Inserts the key as text, e.g. to allow tabs in passwords
+    AcceptAsText,
+}
+
+/// Maps keys to the [`Action`] they trigger while reading a password.
+///
+/// Keys that are not bound are inserted as text if they are printable or pasted, and ignored
+/// otherwise.
+///
+/// On Unix, special characters changed with `stty`, e.g. `stty kill ^X`, replace the key the
+/// preset binds to the matching action when reading from a terminal, and characters disabled with
+/// `stty intr undef` unbind it. Backspace and Ctrl-H erase whatever `stty erase` is set to. Keys
+/// passed to [`Keymap::bind`] or [`Keymap::unbind`] keep their binding.
+///
+/// # Examples
+///
+/// ## Allowing tabs in passwords and cancelling with Esc
+/// ```
+/// use rpassword::{Action, ConfigBuilder, Key, Keymap};
+///
+/// let keymap = Keymap::emacs()
+///     .bind(Key::Tab, Action::AcceptAsText)
+///     .bind(Key::Esc, Action::Cancel)
+///     .unbind(Key::Ctrl('w'));
+///
+/// let config = ConfigBuilder::new().keymap(keymap).build();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keymap {
+    bindings: HashMap<Key, Action>,
+    /// Keys bound or unbound by the caller rather than by a preset, which the terminal's special
+    /// characters do not override
+    explicit: HashSet<Key>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap::emacs()
+    }
+}
+
+impl Keymap {
+    /// Creates a keymap without any binding, where even Enter does not submit the password.
+    pub fn empty() -> Keymap {
+        Keymap {
+            bindings: HashMap::new(),
+            explicit: HashSet::new(),
+        }
+    }
+
+    /// Creates a keymap with the bare minimum: submit, cancel, suspend, end of file and erasing.
+    pub fn minimal() -> Keymap {
+        Keymap::empty()
+            .preset(Key::Enter, Action::Submit)
+            .preset(Key::Ctrl('c'), Action::Cancel)
+            .preset(Key::Ctrl('z'), Action::Suspend)
+            .preset(Key::Ctrl('d'), Action::EndOfFile)
+            .preset(Key::Backspace, Action::EraseChar)
+            .preset(Key::Ctrl('h'), Action::EraseChar)
+            .preset(Key::Ctrl('u'), Action::EraseLine)
+    }
+
+    /// Creates a keymap with readline-like bindings for cursor movement and erasing (the default).
+    pub fn emacs() -> Keymap {
+        Keymap::minimal()
+            .preset(Key::Ctrl('w'), Action::EraseWord)
+            .preset(Key::Ctrl('k'), Action::EraseToEnd)
+            .preset(Key::Delete, Action::EraseCharForward)
+            .preset(Key::Ctrl('a'), Action::MoveHome)
+            .preset(Key::Home, Action::MoveHome)
+            .preset(Key::Ctrl('e'), Action::MoveEnd)
+            .preset(Key::End, Action::MoveEnd)
+            .preset(Key::Ctrl('b'), Action::MoveLeft)
+            .preset(Key::Left, Action::MoveLeft)
+            .preset(Key::Ctrl('f'), Action::MoveRight)
+            .preset(Key::Right, Action::MoveRight)
+            .preset(Key::CtrlLeft, Action::MoveWordLeft)
+            .preset(Key::CtrlRight, Action::MoveWordRight)
+            .preset(Key::Ctrl('v'), Action::LiteralNext)
+            .preset(Key::Ctrl('l'), Action::ClearScreen)
+            .preset(alt(Key::Char('b')), Action::MoveWordLeft)
+            .preset(alt(Key::Left), Action::MoveWordLeft)
+            .preset(alt(Key::Char('f')), Action::MoveWordRight)
+            .preset(alt(Key::Right), Action::MoveWordRight)
+            .preset(alt(Key::Char('d')), Action::EraseWordForward)
+            .preset(alt(Key::Backspace), Action::EraseAlphanumericWord)
+            .preset(alt(Key::Ctrl('h')), Action::EraseAlphanumericWord)
+    }
+
+    /// Binds the key to the action, replacing any previous binding for that key.
+    pub fn bind(mut self, key: Key, action: Action) -> Keymap {
+        self.explicit.insert(key.clone());
+        self.bindings.insert(key, action);
+        self
+    }
+
+    /// Removes the binding for the key, if any.
+    pub fn unbind(mut self, key: Key) -> Keymap {
+        self.bindings.remove(&key);
+        self.explicit.insert(key);
+        self
+    }
+
+    fn preset(mut self, key: Key, action: Action) -> Keymap {
+        self.bindings.insert(key, action);
+        self
+    }
+
+    /// Applies the special characters configured on the terminal, such as `stty kill ^X`, given
+    /// as their action, the key they are usually typed with and the key they are set to, `None`
+    /// if disabled.
+    ///
+    /// Keys that are bound explicitly keep their binding.
+    pub(crate) fn with_terminal_bindings(
+        mut self,
+        bindings: Vec<(Action, Key, Option<Key>)>,
+    ) -> Keymap {
+        // Unbind every usual key first, so that swapped characters do not unbind each other.
+        // Like readline, Backspace and Ctrl-H always erase, whatever `stty erase` is set to.
+        for (action, usual, _) in &bindings {
+            if *action != Action::EraseChar
+                && !self.explicit.contains(usual)
+                && self.bindings.get(usual) == Some(action)
+            {
+                self.bindings.remove(usual);
+            }
+        }
+        for (action, _, key) in bindings {
+            if let Some(key) = key
+                && !self.explicit.contains(&key)
+            {
+                self.bindings.insert(key, action);
+            }
+        }
+        self
+    }
+
+    /// Returns the action the key triggers.
+    pub fn action(&self, key: &Key) -> Action {
+        match self.bindings.get(key) {
+            Some(action) => *action,
+            None if matches!(key, Key::Char(_) | Key::Paste(_)) => Action::AcceptAsText,
+            None => Action::Ignore,
+        }
+    }
+}
+
+fn alt(key: Key) -> Key {
+    Key::Alt(Box::new(key))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::key::Key;
+    use crate::keymap::{Action, Keymap};
+
+    #[test]
+    fn keymap_unbound_keys() {
+        let keymap = Keymap::empty();
+        assert_eq!(keymap.action(&Key::Char('a')), Action::AcceptAsText);
+        assert_eq!(keymap.action(&Key::Enter), Action::Ignore);
+        assert_eq!(keymap.action(&Key::Ctrl('w')), Action::Ignore);
+    }
+
+    #[test]
+    fn keymap_minimal() {
+        let keymap = Keymap::minimal();
+        assert_eq!(keymap.action(&Key::Enter), Action::Submit);
+        assert_eq!(keymap.action(&Key::Ctrl('c')), Action::Cancel);
+        assert_eq!(keymap.action(&Key::Ctrl('h')), Action::EraseChar);
+        assert_eq!(keymap.action(&Key::Ctrl('w')), Action::Ignore);
+        assert_eq!(keymap.action(&Key::Left), Action::Ignore);
+    }
+
+    #[test]
+    fn keymap_bind_and_unbind() {
+        let keymap = Keymap::emacs()
+            .bind(Key::Tab, Action::AcceptAsText)
+            .bind(Key::Char(' '), Action::Ignore)
+            .unbind(Key::Ctrl('w'));
+        assert_eq!(keymap.action(&Key::Tab), Action::AcceptAsText);
+        assert_eq!(keymap.action(&Key::Char(' ')), Action::Ignore);
+        assert_eq!(keymap.action(&Key::Ctrl('w')), Action::Ignore);
+        assert_eq!(
+            keymap.action(&Key::Alt(Box::new(Key::Char('b')))),
+            Action::MoveWordLeft
+        );
+    }
+
+    #[test]
+    fn keymap_with_terminal_bindings() {
+        let keymap = Keymap::emacs()
+            .bind(Key::Ctrl('w'), Action::EraseWord)
+            .with_terminal_bindings(vec![
+                (Action::EraseLine, Key::Ctrl('u'), Some(Key::Ctrl('x'))),
+                (Action::Cancel, Key::Ctrl('c'), Some(Key::Ctrl('w'))),
+            ]);
+        assert_eq!(keymap.action(&Key::Ctrl('x')), Action::EraseLine);
+        assert_eq!(keymap.action(&Key::Ctrl('u')), Action::Ignore);
+        assert_eq!(keymap.action(&Key::Ctrl('w')), Action::EraseWord);
+        assert_eq!(keymap.action(&Key::Ctrl('c')), Action::Ignore);
+    }
+
+    #[test]
+    fn keymap_terminal_bindings_override_presets() {
+        // stty werase ^A
+        let keymap = Keymap::emacs().with_terminal_bindings(vec![(
+            Action::EraseWord,
+            Key::Ctrl('w'),
+            Some(Key::Ctrl('a')),
+        )]);
+        assert_eq!(keymap.action(&Key::Ctrl('a')), Action::EraseWord);
+        assert_eq!(keymap.action(&Key::Ctrl('w')), Action::Ignore);
+        assert_eq!(keymap.action(&Key::Home), Action::MoveHome);
+    }
+
+    #[test]
+    fn keymap_terminal_bindings_keep_erase_keys() {
+        // stty erase ^H
+        let keymap = Keymap::emacs().with_terminal_bindings(vec![(
+            Action::EraseChar,
+            Key::Backspace,
+            Some(Key::Ctrl('h')),
+        )]);
+        assert_eq!(keymap.action(&Key::Backspace), Action::EraseChar);
+        assert_eq!(keymap.action(&Key::Ctrl('h')), Action::EraseChar);
+
+        // stty erase ^X
+        let keymap = Keymap::minimal().with_terminal_bindings(vec![(
+            Action::EraseChar,
+            Key::Backspace,
+            Some(Key::Ctrl('x')),
+        )]);
+        assert_eq!(keymap.action(&Key::Ctrl('x')), Action::EraseChar);
+        assert_eq!(keymap.action(&Key::Backspace), Action::EraseChar);
+    }
+
+    #[test]
+    fn keymap_terminal_bindings_disable_and_remap() {
+        // stty intr undef
+        let keymap =
+            Keymap::emacs().with_terminal_bindings(vec![(Action::Cancel, Key::Ctrl('c'), None)]);
+        assert_eq!(keymap.action(&Key::Ctrl('c')), Action::Ignore);
+
+        // stty intr ^X
+        let keymap = Keymap::emacs().with_terminal_bindings(vec![(
+            Action::Cancel,
+            Key::Ctrl('c'),
+            Some(Key::Ctrl('x')),
+        )]);
+        assert_eq!(keymap.action(&Key::Ctrl('x')), Action::Cancel);
+        assert_eq!(keymap.action(&Key::Ctrl('c')), Action::Ignore);
+
+        // stty intr ^Z susp ^C
+        let keymap = Keymap::emacs().with_terminal_bindings(vec![
+            (Action::Cancel, Key::Ctrl('c'), Some(Key::Ctrl('z'))),
+            (Action::Suspend, Key::Ctrl('z'), Some(Key::Ctrl('c'))),
+        ]);
+        assert_eq!(keymap.action(&Key::Ctrl('z')), Action::Cancel);
+        assert_eq!(keymap.action(&Key::Ctrl('c')), Action::Suspend);
+    }
+}
@@ -29,21 +29,41 @@
 //! let password = rpassword::read_password_with_config(config).unwrap();
 //! println!("Your password is {}", password);
 //! ```
+//!
+//! Applications that handle the terminal themselves, such as TUIs, can feed keys to a
+//! [`PasswordEditor`] instead. With the `crossterm` feature, crossterm key events convert into
+//! [`Key`], and with the `ratatui` feature, `PasswordField` draws the password being typed.
 
 use rtoolbox::fix_line_issues::fix_line_issues;
 use rtoolbox::print_tty::print_writer;
 use rtoolbox::safe_string::SafeString;
-use std::fs::OpenOptions;
 use std::io;
-use std::io::{BufRead, Cursor, Write};
+use std::io::{BufRead, Write};
+use std::time::{Duration, Instant};
 
 mod config;
+#[cfg(feature = "crossterm")]
+mod crossterm_keys;
+mod editor;
 mod feedback;
+mod key;
+mod keymap;
+#[cfg(feature = "ratatui")]
+mod password_field;
+mod renderer;
+mod terminfo;
+mod theme;
 
 #[cfg(all(target_family = "unix", not(target_family = "wasm")))]
 mod unix;
 #[cfg(all(target_family = "unix", not(target_family = "wasm")))]
 use unix::*;
+#[cfg(all(target_family = "unix", not(target_family = "wasm")))]
+mod raw_mode;
+#[cfg(all(target_family = "unix", not(target_family = "wasm")))]
+mod restore;
+#[cfg(all(target_family = "unix", not(target_family = "wasm")))]
+pub use raw_mode::{RawMode, RawModeGuard};
 
 #[cfg(target_family = "windows")]
 mod windows;
@@ -57,140 +77,276 @@ mod wasm;
 #[cfg(target_family = "wasm")]
 use wasm::*;
 
-use crate::config::{OutputTarget, PasswordFeedback};
-use crate::feedback::FeedbackState;
-pub use config::{Config, ConfigBuilder};
-
-const BACKSPACE: char = '\x08';
-const DEL: char = '\x7F';
-const CTRL_C: char = '\x03';
-const CTRL_D: char = '\x04';
-const CTRL_U: char = '\x15';
-const CTRL_W: char = '\x17';
-const ESC: char = '\x1B';
-
-trait RawPasswordInput {
-    fn new(config: Config) -> io::Result<impl RawPasswordInput>;
+use crate::config::ReadOptions;
+use crate::key::{CharSource, read_key};
+use crate::terminfo::Capabilities;
+pub use config::{
+    AfterSubmit, Config, ConfigBuilder, OnCancel, PasswordFeedback, PastePolicy, TerminalFallback,
+    Typeahead,
+};
+pub use editor::{Edit, EditStatus, PasswordEditor};
+pub use key::{Key, KeyReader};
+pub use keymap::{Action, Keymap};
+#[cfg(feature = "ratatui")]
+pub use password_field::PasswordField;
+pub use renderer::{CharFeedback, FeedbackEvent, FeedbackRenderer};
+pub use theme::{Color, Style, Theme};
+
+trait RawPasswordInput: CharSource {
+    fn new(config: Config) -> io::Result<Self>
+    where
+        Self: Sized;
     fn needs_terminal_configuration(&self) -> bool;
     fn apply_terminal_configuration(&mut self) -> io::Result<()>;
-    fn read_char(&mut self) -> std::io::Result<char>;
     fn write_output(&mut self, output: &str) -> std::io::Result<()>;
     fn send_signal_sigint(&mut self) -> std::io::Result<()>;
 
+    /// Returns the special characters the terminal is configured with for editing that differ
+    /// from the usual ones, e.g. with `stty erase ^H`, as their action, their usual key and their
+    /// key if not disabled
+    fn terminal_bindings(&self) -> Vec<(Action, Key, Option<Key>)> {
+        Vec::new()
+    }
+
+    /// Waits up to `timeout`, or forever, for a key to be typed, returning whether one can be
+    /// read. Returns early when the terminal is resized.
+    fn wait_for_key(&mut self, timeout: Option<Duration>) -> io::Result<bool> {
+        match timeout {
+            Some(timeout) => self.poll_char(timeout),
+            None => Ok(true),
+        }
+    }
+
+    /// Discards the input typed before the password is read, returning whether there was any
+    fn discard_typeahead(&mut self) -> io::Result<bool> {
+        Ok(false)
+    }
+
+    /// Returns whether the terminal was resized since the last call
+    fn resized(&mut self) -> bool {
+        false
+    }
+
+    /// Returns whether the program was asked to stop, e.g. with `SIGTSTP`, since the last call
+    fn stop_requested(&mut self) -> bool {
+        false
+    }
+
+    /// Restores the terminal, stops the program until it is continued, then configures the
+    /// terminal again
+    fn stop(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Returns whether the program was continued after being stopped without being asked to, e.g.
+    /// with `SIGSTOP`, since the last call, configuring the terminal again if so
+    fn continued(&mut self) -> io::Result<bool> {
+        Ok(false)
+    }
+
+    /// Returns the number of columns of the terminal the password is drawn on, if known
+    fn terminal_columns(&self) -> Option<usize> {
+        None
+    }
+
+    /// Returns whether the output goes to a terminal that understands colours and other styles
+    fn supports_styling(&self) -> bool {
+        false
+    }
+
+    /// Returns what the terminal the password is drawn on supports, given the configured name of
+    /// the terminal, ANSI terminals by default
+    fn capabilities(&self, _terminal: Option<&str>) -> Capabilities {
+        Capabilities::ansi()
+    }
+
+    /// Asks the program to stop like the terminal would on Ctrl-Z, for platforms with job control
+    fn send_signal_sigtstp(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Returns whether the terminal reports pastes with `ESC[200~` and `ESC[201~` once asked to
+    fn supports_bracketed_paste(&self) -> bool {
+        self.needs_terminal_configuration()
+    }
+
     /// Reads a password from the console using the given config
-    fn read_password(&mut self, password_feedback: PasswordFeedback) -> std::io::Result<String> {
+    fn read_password(&mut self, options: ReadOptions) -> std::io::Result<PasswordEntry> {
         if self.needs_terminal_configuration() {
             self.apply_terminal_configuration()?;
+
+            // Keys are only discarded once raw mode is on, so that none is left in a line buffer
+            if options.typeahead != Typeahead::Keep
+                && self.discard_typeahead()?
+                && options.typeahead == Typeahead::Warn
+            {
+                self.write_output("Input typed before the prompt was discarded\n")?;
+            }
         }
 
-        let mut state = FeedbackState::new(password_feedback, self.needs_terminal_configuration());
+        let mut keymap = options.keymap;
+        if let Some(key) = options.reveal_key {
+            keymap = keymap.bind(key, Action::Reveal);
+        }
+        let keymap = keymap.with_terminal_bindings(self.terminal_bindings());
+        let capabilities = self.capabilities(options.terminal.as_deref());
+        let mut theme = options.theme;
+        if !self.supports_styling() || !capabilities.colors || !theme::styling_allowed() {
+            theme = theme.without_styles();
+        }
+        let renderer = if capabilities.can_erase() {
+            options.renderer.unwrap_or_else(|| {
+                renderer::renderer_with_mask(options.password_feedback, theme.mask.clone())
+            })
+        } else {
+            // Feedback that cannot be erased would be left on screen
+            renderer::renderer(PasswordFeedback::Hide)
+        };
+        let bracketed_paste = self.supports_bracketed_paste() && capabilities.bracketed_paste;
+        let mut editor =
+            PasswordEditor::with_output(renderer, keymap, self.needs_terminal_configuration())
+                .paste_policy(options.paste_policy.clone())
+                .after_submit(options.after_submit)
+                .reveal_timeout(options.reveal_timeout)
+                .prompt(&options.prompt)
+                .theme(theme)
+                .capabilities(capabilities);
+        if let Some(columns) = self.terminal_columns() {
+            editor = editor.terminal_width(columns);
+        }
 
-        loop {
-            let c = match self.read_char() {
-                Ok(c) => c,
-                Err(e) => {
-                    if e.kind() == std::io::ErrorKind::UnexpectedEof {
-                        break;
-                    }
-                    return Err(e);
-                }
-            };
-            match c {
-                // LF / CR (Enter)
-                '\n' | '\r' => {
-                    let output = state.finish();
-                    if !output.is_empty() {
-                        self.write_output(output.as_str())?;
-                    }
-                    break;
-                }
-                // Backspace / DEL
-                DEL | BACKSPACE => {
-                    let output = state.pop_char();
-                    if !output.is_empty() {
-                        self.write_output(output.as_str())?;
-                    }
-                }
-                // Ctrl-U: clear line
-                CTRL_U => {
-                    let output = state.clear();
-                    if !output.is_empty() {
-                        self.write_output(output.as_str())?;
-                    }
-                }
-                // Ctrl-W: clear to last space
-                CTRL_W => {
-                    let output = state.clear_til_last_space();
-                    if !output.is_empty() {
-                        self.write_output(output.as_str())?;
-                    }
+        let prompt = editor.prompt_output();
+        if !prompt.is_empty() {
+            self.write_output(&prompt)?;
+        }
+
+        if bracketed_paste {
+            self.write_output("\x1B[?2004h")?;
+        }
+        let status = self.edit_password(&mut editor, options.escape_timeout, bracketed_paste);
+        if bracketed_paste {
+            self.write_output("\x1B[?2004l")?;
+        }
+
+        match status? {
+            EditStatus::Cancelled => {
+                match options.on_cancel {
+                    OnCancel::RaiseSigint => self.send_signal_sigint()?,
+                    OnCancel::ReturnError => {}
+                    OnCancel::Callback(callback) => callback(),
                 }
-                // Ctrl-C: interrupt
-                CTRL_C => {
-                    let output = state.abort();
-                    if !output.is_empty() {
-                        self.write_output(output.as_str())?;
-                    }
-                    self.send_signal_sigint()?;
-                    return Err(std::io::Error::new(
-                        std::io::ErrorKind::Interrupted,
-                        "interrupted",
-                    ));
+                Err(std::io::Error::new(
+                    std::io::ErrorKind::Interrupted,
+                    Cancelled,
+                ))
+            }
+            EditStatus::EndOfFile => Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "unexpected end of file",
+            )),
+            EditStatus::PasteRejected => {
+                let message = match options.paste_policy {
+                    PastePolicy::Reject(message) => message,
+                    _ => "pasting is not allowed".to_string(),
+                };
+                Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    message,
+                ))
+            }
+            _ => Ok(PasswordEntry {
+                pasted: editor.was_pasted(),
+                password: editor.into_password(),
+            }),
+        }
+    }
+
+    /// Feeds keys to the editor until the user is done, returning how editing ended
+    fn edit_password(
+        &mut self,
+        editor: &mut PasswordEditor,
+        escape_timeout: Duration,
+        bracketed_paste: bool,
+    ) -> std::io::Result<EditStatus> {
+        loop {
+            if self.resized()
+                && let Some(columns) = self.terminal_columns()
+            {
+                self.write_output(editor.resize(columns).output.as_str())?;
+            }
+
+            let stop = self.stop_requested();
+            if stop || self.continued()? {
+                // Whatever runs meanwhile, e.g. the shell, writes below the password
+                let mut output = editor.suspend().output;
+                if bracketed_paste {
+                    output.push_str("\x1B[?2004l");
                 }
-                // Ctrl-D: EOF when empty
-                CTRL_D => {
-                    if state.is_empty() {
-                        return Err(std::io::Error::new(
-                            std::io::ErrorKind::UnexpectedEof,
-                            "unexpected end of file",
-                        ));
-                    }
+                self.write_output(&output)?;
+                if stop {
+                    self.stop()?;
                 }
-                // ESC: consume and discard escape sequence like arrow keys
-                ESC => {
-                    let c = match self.read_char() {
-                        Ok(c) => c,
-                        Err(e) => {
-                            if e.kind() == std::io::ErrorKind::UnexpectedEof {
-                                break;
-                            }
-                            return Err(e);
-                        }
-                    };
-
-                    if c == '[' || c == 'O' {
-                        // CSI (ESC [) or SS3 (ESC O): read until final byte (0x40-0x7E)
-                        loop {
-                            let c = match self.read_char() {
-                                Ok(c) => c,
-                                Err(e) => {
-                                    if e.kind() == std::io::ErrorKind::UnexpectedEof {
-                                        break;
-                                    }
-                                    return Err(e);
-                                }
-                            };
-                            if ('\x40'..='\x7E').contains(&c) {
-                                break;
-                            }
-                        }
-                    }
+                let mut output = editor.resume(self.terminal_columns()).output;
+                if bracketed_paste {
+                    output.push_str("\x1B[?2004h");
                 }
-                c if !c.is_control() => {
-                    let output = state.push_char(c);
-                    if !output.is_empty() {
-                        self.write_output(output.as_str())?;
-                    }
+                self.write_output(&output)?;
+            }
+
+            let timeout = editor
+                .deadline()
+                .map(|deadline| deadline.saturating_duration_since(Instant::now()));
+            if !self.wait_for_key(timeout)? {
+                // Waiting stops early on resize, the editor then does nothing
+                let edit = editor.handle_timeout();
+                if !edit.output.is_empty() {
+                    self.write_output(edit.output.as_str())?;
                 }
-                // Discard unrecognized control characters and invalid input
-                _ => {}
+                continue;
+            }
+
+            let Some(key) = read_key(self, escape_timeout)? else {
+                return Ok(EditStatus::Submitted);
+            };
+            let edit = editor.handle_key(key);
+            if !edit.output.is_empty() {
+                self.write_output(edit.output.as_str())?;
+            }
+
+            match edit.status {
+                EditStatus::Editing => {}
+                // The program stops once the signal is handled, like with `ISIG`
+                EditStatus::Suspended => self.send_signal_sigtstp()?,
+                status => return Ok(status),
             }
         }
+    }
+}
+
+/// A password read from the terminal, along with how it was entered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PasswordEntry {
+    pub password: String,
+    /// Whether any part of the password was pasted, which the terminal reports when it supports
+    /// bracketed paste mode
+    pub pasted: bool,
+}
 
-        Ok(state.into_password())
+/// The error behind the [`std::io::ErrorKind::Interrupted`] error returned when typing is
+/// cancelled, e.g. with Ctrl-C, see [`OnCancel`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Cancelled;
+
+impl std::fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("interrupted")
     }
 }
 
+impl std::error::Error for Cancelled {}
+
 /// Reads a password from `impl BufRead`.
 ///
 /// **Deprecated**: This method is deprecated. Use `read_password_with_config` with a temporary file instead.
@@ -256,9 +412,14 @@ pub fn prompt_password_from_bufread(
 
 /// Reads a password from TTY using the given config
 pub fn read_password_with_config(config: Config) -> std::io::Result<String> {
-    let password_feedback = config.password_feedback;
+    read_password_entry_with_config(config).map(|entry| entry.password)
+}
+
+/// Reads a password from TTY using the given config, also telling whether it was pasted
+pub fn read_password_entry_with_config(mut config: Config) -> std::io::Result<PasswordEntry> {
+    let options = std::mem::take(&mut config.options);
     let mut raw_mode_input = RawModeInput::new(config)?;
-    raw_mode_input.read_password(password_feedback)
+    raw_mode_input.read_password(options)
 }
 
 /// Reads a password from the TTY
@@ -274,27 +435,27 @@ pub fn prompt_password(prompt: impl ToString) -> std::io::Result<String> {
 /// Prompts and then reads a password using the given config
 pub fn prompt_password_with_config(
     prompt: impl ToString,
-    mut config: Config,
+    config: Config,
 ) -> std::io::Result<String> {
-    {
-        // Create an inner scope to allow using the config without moving it
-        // The mut ref to the config is dropped at the end of the scope
-        let mut output: Box<dyn Write> = match &mut config.output {
-            OutputTarget::FilePath(path) => Box::new(OpenOptions::new().write(true).open(path)?),
-            OutputTarget::Writer(writer) => Box::new(writer),
-            OutputTarget::Void => Box::new(Cursor::new(Vec::<u8>::new())), // TODO: Should use a SafeVec instead
-        };
-        output.write_all(prompt.to_string().as_bytes())?;
-        output.flush()?;
-    }
+    prompt_password_entry_with_config(prompt, config).map(|entry| entry.password)
+}
 
-    read_password_with_config(config)
+/// Prompts and then reads a password using the given config, also telling whether it was pasted
+pub fn prompt_password_entry_with_config(
+    prompt: impl ToString,
+    mut config: Config,
+) -> std::io::Result<PasswordEntry> {
+    // The reader writes the prompt, so that it can write it again when repainting
+    config.options.prompt = prompt.to_string();
+    read_password_entry_with_config(config)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::io::Cursor;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
 
     fn mock_input_crlf() -> Cursor<&'static [u8]> {
         Cursor::new(&b"A mocked response.\r\nAnother mocked response.\r\n"[..])
@@ -346,4 +507,174 @@ mod tests {
         let result = read_password_with_config(config);
         assert_eq!("hello wo", result.unwrap());
     }
+
+    #[test]
+    fn test_read_password_with_config_returns_cancelled() {
+        let config = ConfigBuilder::new()
+            .input_data("ab\x03")
+            .output_discard()
+            .on_cancel(OnCancel::ReturnError)
+            .build();
+
+        let err = read_password_with_config(config).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::Interrupted);
+        assert!(err.get_ref().is_some_and(|err| err.is::<Cancelled>()));
+        assert_eq!(err.to_string(), "interrupted");
+    }
+
+    #[test]
+    fn test_read_password_with_config_calls_on_cancel() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let config = ConfigBuilder::new()
+            .input_data("ab\x03")
+            .output_discard()
+            .on_cancel(OnCancel::callback(move || {
+                counter.fetch_add(1, Ordering::Relaxed);
+            }))
+            .build();
+
+        let err = read_password_with_config(config).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::Interrupted);
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_read_password_with_config_with_keymap() {
+        let keymap = Keymap::minimal()
+            .bind(Key::Tab, Action::AcceptAsText)
+            .bind(Key::Ctrl('x'), Action::EraseLine)
+            .bind(Key::Ctrl('v'), Action::LiteralNext);
+        let config = ConfigBuilder::new()
+            .input_data("nope\x18a\tb\x17\x16\x17\x1B[D\n")
+            .output_discard()
+            .keymap(keymap)
+            .build();
+
+        let result = read_password_with_config(config);
+        assert_eq!("a\tb\x17", result.unwrap());
+    }
+
+    #[test]
+    fn test_read_password_with_config_discards_terminal_replies() {
+        let config = ConfigBuilder::new()
+            .input_data("pass\x1B]11;rgb:0/0/0\x1B\\\x1B_Gi=1;OK\x1B\\word\n")
+            .output_discard()
+            .build();
+
+        let result = read_password_with_config(config);
+        assert_eq!("password", result.unwrap());
+    }
+
+    #[test]
+    fn test_read_password_with_config_with_paste() {
+        let config = ConfigBuilder::new()
+            .input_data("my \x1B[200~password\r\n\x1B[201~\n")
+            .output_discard()
+            .build();
+
+        let result = read_password_with_config(config);
+        assert_eq!("my password", result.unwrap());
+
+        let config = ConfigBuilder::new()
+            .input_data("my \x1B[200~pass\nword\x1B[201~\n")
+            .paste_policy(PastePolicy::StripControlCharacters)
+            .output_discard()
+            .build();
+
+        let result = read_password_with_config(config);
+        assert_eq!("my password", result.unwrap());
+    }
+
+    #[test]
+    fn test_read_password_entry_with_config_reports_paste() {
+        let config = ConfigBuilder::new()
+            .input_data("\x1B[200~password\r\n\x1B[201~\n")
+            .output_discard()
+            .build();
+        let entry = read_password_entry_with_config(config).unwrap();
+        assert_eq!("password", entry.password);
+        assert!(entry.pasted);
+
+        let config = ConfigBuilder::new()
+            .input_data("password\n")
+            .output_discard()
+            .build();
+        let entry = read_password_entry_with_config(config).unwrap();
+        assert_eq!("password", entry.password);
+        assert!(!entry.pasted);
+    }
+
+    #[test]
+    fn test_read_password_with_config_with_paste_policy() {
+        let config = ConfigBuilder::new()
+            .input_data("\x1B[200~pass\nword\n\x1B[201~\n")
+            .output_discard()
+            .paste_policy(PastePolicy::Allow)
+            .build();
+        let result = read_password_with_config(config);
+        assert_eq!("pass\nword\n", result.unwrap());
+
+        let config = ConfigBuilder::new()
+            .input_data("\x1B[200~password\x1B[201~\n")
+            .output_discard()
+            .paste_policy(PastePolicy::Reject("type it instead".to_string()))
+            .build();
+        let err = read_password_with_config(config).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "type it instead");
+    }
+
+    #[test]
+    fn test_read_password_with_config_with_reveal() {
+        let config = ConfigBuilder::new()
+            .input_data("pass\x12word\x12\n")
+            .output_discard()
+            .allow_reveal()
+            .build();
+        let result = read_password_with_config(config);
+        assert_eq!("password", result.unwrap());
+
+        // Revealing is off by default, so Ctrl-R is ignored like any other unbound control key
+        let config = ConfigBuilder::new()
+            .input_data("pass\x12word\n")
+            .output_discard()
+            .build();
+        let result = read_password_with_config(config);
+        assert_eq!("password", result.unwrap());
+    }
+
+    #[test]
+    fn test_read_password_with_config_with_flash_last() {
+        let config = ConfigBuilder::new()
+            .input_data("pass\x7Fsword\n")
+            .output_discard()
+            .password_feedback_flash_last('*', Duration::from_secs(1))
+            .build();
+
+        let result = read_password_with_config(config);
+        assert_eq!("password", result.unwrap());
+    }
+
+    #[test]
+    fn test_read_password_with_config_erases_graphemes() {
+        let config = ConfigBuilder::new()
+            .input_data("cafe\u{301}\x7F👩\u{200D}💻\x7F!\n")
+            .output_discard()
+            .build();
+
+        let result = read_password_with_config(config);
+        assert_eq!("caf!", result.unwrap());
+    }
+
+    #[test]
+    fn test_read_password_with_config_with_cursor_movement() {
+        let config = ConfigBuilder::new()
+            .input_data("hllo\x1B[D\x1B[D\x1B[De\x01\x1B[3~H\x05\x1B[1;5D\x1Bf\x02\x0B!\n")
+            .output_discard()
+            .build();
+
+        let result = read_password_with_config(config);
+        assert_eq!("Hell!", result.unwrap());
+    }
 }
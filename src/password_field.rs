@@ -0,0 +1,207 @@
+use crate::editor::PasswordEditor;
+use ratatui::buffer::Buffer;
+use ratatui::layout::{Position, Rect};
+use ratatui::style::Style;
+use ratatui::text::Span;
+use ratatui::widgets::Widget;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// A ratatui widget drawing a password being typed in a [`PasswordEditor`].
+///
+/// The password is drawn with the feedback of the editor, e.g. nothing with
+/// `PasswordFeedback::Hide` or stars with `PasswordFeedback::Mask('*')`. When it does not fit in
+/// the area, it scrolls horizontally to keep the cursor visible.
+///
+/// # Examples
+/// ```
+/// use ratatui::buffer::Buffer;
+/// use ratatui::layout::Rect;
+/// use ratatui::widgets::Widget;
+/// use rpassword::{Key, PasswordEditor, PasswordFeedback, PasswordField};
+///
+/// let mut editor = PasswordEditor::new(PasswordFeedback::Mask('*'));
+/// editor.handle_key(Key::Char('a'));
+///
+/// let area = Rect::new(0, 0, 12, 1);
+/// let mut buffer = Buffer::empty(area);
+/// PasswordField::new(&editor).prompt("Password: ").render(area, &mut buffer);
+/// assert_eq!(buffer, Buffer::with_lines(["Password: * "]));
+/// ```
+pub struct PasswordField<'a> {
+    editor: &'a PasswordEditor,
+    prompt: Span<'a>,
+    style: Style,
+}
+
+impl<'a> PasswordField<'a> {
+    pub fn new(editor: &'a PasswordEditor) -> PasswordField<'a> {
+        PasswordField {
+            editor,
+            prompt: Span::default(),
+            style: Style::default(),
+        }
+    }
+
+    /// Sets the text drawn before the password.
+    pub fn prompt(self, prompt: impl Into<Span<'a>>) -> PasswordField<'a> {
+        PasswordField {
+            prompt: prompt.into(),
+            ..self
+        }
+    }
+
+    /// Sets the style of the password.
+    pub fn style(self, style: Style) -> PasswordField<'a> {
+        PasswordField { style, ..self }
+    }
+
+    /// Returns where the terminal cursor goes when the field is drawn in `area`, to be passed
+    /// to `Frame::set_cursor_position`.
+    pub fn cursor_position(&self, area: Rect) -> Position {
+        let (x, _) = self.layout(area);
+        let cursor = self.editor.cursor() - self.scroll(area);
+        Position::new(x + cursor as u16, area.y)
+    }
+
+    /// Returns where the password starts and how many columns it can use
+    fn layout(&self, area: Rect) -> (u16, usize) {
+        let prompt_width = (self.prompt.width() as u16).min(area.width);
+        (area.x + prompt_width, (area.width - prompt_width) as usize)
+    }
+
+    /// Returns how many cells of the password are scrolled out on the left, never more than are
+    /// before the cursor, even when the prompt leaves no room for the password
+    fn scroll(&self, area: Rect) -> usize {
+        let (_, width) = self.layout(area);
+        let cursor = self.editor.cursor();
+        (cursor + 1).saturating_sub(width).min(cursor)
+    }
+}
+
+impl Widget for PasswordField<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        if area.is_empty() {
+            return;
+        }
+
+        let (x, width) = self.layout(area);
+        buf.set_span(area.x, area.y, &self.prompt, area.width);
+        if width == 0 {
+            return;
+        }
+        let display = self.editor.display();
+        let scroll = self.scroll(area);
+        // Skip whole graphemes, leaving blank the half of a wide char that is scrolled out
+        let mut skipped = 0;
+        let mut graphemes = display.graphemes(true);
+        while skipped < scroll
+            && let Some(grapheme) = graphemes.next()
+        {
+            skipped += grapheme.width();
+        }
+        let blank = skipped.saturating_sub(scroll);
+        let visible: String = graphemes.collect();
+        buf.set_stringn(
+            x + blank as u16,
+            area.y,
+            visible,
+            width.saturating_sub(blank),
+            self.style,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::PasswordFeedback;
+    use crate::editor::PasswordEditor;
+    use crate::key::Key;
+    use crate::password_field::PasswordField;
+    use ratatui::buffer::Buffer;
+    use ratatui::layout::{Position, Rect};
+    use ratatui::widgets::Widget;
+
+    fn draw(field: PasswordField, width: u16) -> Buffer {
+        let mut buffer = Buffer::empty(Rect::new(0, 0, width, 1));
+        field.render(buffer.area, &mut buffer);
+        buffer
+    }
+
+    fn typed(feedback: PasswordFeedback, typed: &str) -> PasswordEditor {
+        let mut editor = PasswordEditor::new(feedback);
+        for c in typed.chars() {
+            editor.handle_key(Key::Char(c));
+        }
+        editor
+    }
+
+    #[test]
+    fn password_field_feedback() {
+        let editor = typed(PasswordFeedback::Hide, "abc");
+        assert_eq!(
+            draw(PasswordField::new(&editor), 4),
+            Buffer::with_lines(["    "])
+        );
+
+        let editor = typed(PasswordFeedback::PartialMask('*', 1), "abc");
+        assert_eq!(
+            draw(PasswordField::new(&editor), 4),
+            Buffer::with_lines(["a** "])
+        );
+    }
+
+    #[test]
+    fn password_field_scrolls_to_cursor() {
+        let mut editor = typed(PasswordFeedback::PartialMask('#', 6), "abcdef");
+        let field = PasswordField::new(&editor).prompt("> ");
+        assert_eq!(
+            field.cursor_position(Rect::new(0, 0, 6, 1)),
+            Position::new(5, 0)
+        );
+        assert_eq!(draw(field, 6), Buffer::with_lines(["> def "]));
+
+        editor.handle_key(Key::Home);
+        let field = PasswordField::new(&editor).prompt("> ");
+        assert_eq!(
+            field.cursor_position(Rect::new(0, 0, 6, 1)),
+            Position::new(2, 0)
+        );
+        assert_eq!(draw(field, 6), Buffer::with_lines(["> abcd"]));
+    }
+
+    #[test]
+    fn password_field_scrolls_wide_chars() {
+        let editor = typed(PasswordFeedback::PartialMask('#', 6), "密码ab");
+        let field = PasswordField::new(&editor).prompt("> ");
+        assert_eq!(
+            field.cursor_position(Rect::new(0, 0, 6, 1)),
+            Position::new(5, 0)
+        );
+        // The first half of 码 is scrolled out, so none of it is drawn
+        assert_eq!(draw(field, 6), Buffer::with_lines([">  ab "]));
+    }
+
+    #[test]
+    fn password_field_prompt_fills_area() {
+        let editor = typed(PasswordFeedback::Mask('*'), "a");
+        let field = PasswordField::new(&editor).prompt("Password: ");
+        assert_eq!(
+            field.cursor_position(Rect::new(0, 0, 10, 1)),
+            Position::new(10, 0)
+        );
+        assert_eq!(
+            field.cursor_position(Rect::new(0, 0, 4, 1)),
+            Position::new(4, 0)
+        );
+    }
+
+    #[test]
+    fn password_field_renders_in_narrow_area() {
+        let editor = typed(PasswordFeedback::Mask('*'), "ab");
+        let field = || PasswordField::new(&editor).prompt("Password: ");
+        assert_eq!(draw(field(), 10), Buffer::with_lines(["Password: "]));
+        assert_eq!(draw(field(), 4), Buffer::with_lines(["Pass"]));
+        assert_eq!(draw(field(), 11), Buffer::with_lines(["Password:  "]));
+    }
+}
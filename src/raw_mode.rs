@@ -0,0 +1,253 @@
+use libc::{
+    ECHO, ECHONL, ICANON, ISIG, IXON, TCSANOW, VMIN, VTIME, c_int, tcflag_t, tcsetattr, termios,
+};
+use std::io;
+use std::mem;
+use std::os::fd::{AsFd, AsRawFd, OwnedFd};
+use std::time::Duration;
+
+pub(crate) fn safe_tcgetattr(fd: c_int) -> io::Result<termios> {
+    let mut term = mem::MaybeUninit::<termios>::uninit();
+    if unsafe { libc::tcgetattr(fd, term.as_mut_ptr()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { term.assume_init() })
+}
+
+pub(crate) fn safe_tcsetattr(fd: c_int, term: &termios) -> io::Result<()> {
+    if unsafe { tcsetattr(fd, TCSANOW, term) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn set_flag(flags: &mut tcflag_t, flag: tcflag_t, on: bool) {
+    if on {
+        *flags |= flag;
+    } else {
+        *flags &= !flag;
+    }
+}
+
+/// Which terminal features stay on while a [`RawModeGuard`] is alive, the way passwords are read
+/// by default: without echo, line editing, signals nor flow control, returning every byte as soon
+/// as it is typed. Only on Unix.
+///
+/// # Examples
+///
+/// ## Waiting for any key
+/// ```no_run
+/// use std::io::Read;
+/// use rpassword::RawMode;
+///
+/// let mut tty = std::fs::File::open("/dev/tty")?;
+/// // Ctrl-C still interrupts the program
+/// let guard = RawMode::new().signals(true).apply(&tty)?;
+/// tty.read_exact(&mut [0])?;
+/// drop(guard);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+///
+/// ## Reading a line without echo
+/// ```no_run
+/// use rpassword::RawMode;
+///
+/// let _guard = RawMode::new()
+///     .canonical(true)
+///     .signals(true)
+///     .flow_control(true)
+///     .apply(std::io::stdin())?;
+/// let mut line = String::new();
+/// std::io::stdin().read_line(&mut line)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RawMode {
+    echo: bool,
+    canonical: bool,
+    signals: bool,
+    flow_control: bool,
+    min_bytes: u8,
+    timeout: u8,
+}
+
+impl Default for RawMode {
+    fn default() -> Self {
+        RawMode {
+            echo: false,
+            canonical: false,
+            signals: false,
+            flow_control: false,
+            min_bytes: 1,
+            timeout: 0,
+        }
+    }
+}
+
+impl RawMode {
+    /// Creates the mode passwords are read with.
+    pub fn new() -> RawMode {
+        RawMode::default()
+    }
+
+    /// Shows what is typed (`ECHO`).
+    pub fn echo(self, echo: bool) -> RawMode {
+        RawMode { echo, ..self }
+    }
+
+    /// Reads line by line, the terminal handling erasing (`ICANON`).
+    pub fn canonical(self, canonical: bool) -> RawMode {
+        RawMode { canonical, ..self }
+    }
+
+    /// Turns Ctrl-C, Ctrl-\ and Ctrl-Z into signals (`ISIG`).
+    pub fn signals(self, signals: bool) -> RawMode {
+        RawMode { signals, ..self }
+    }
+
+    /// Stops the output on Ctrl-S until Ctrl-Q (`IXON`).
+    pub fn flow_control(self, flow_control: bool) -> RawMode {
+        RawMode {
+            flow_control,
+            ..self
+        }
+    }
+
+    /// Sets how many bytes a read waits for outside of canonical mode (`VMIN`), 1 by default.
+    pub fn min_bytes(self, min_bytes: u8) -> RawMode {
+        RawMode { min_bytes, ..self }
+    }
+
+    /// Sets how long a read waits for bytes outside of canonical mode (`VTIME`), rounded down to
+    /// tenths of a second up to 25.5 seconds. No timeout by default.
+    pub fn timeout(self, timeout: Duration) -> RawMode {
+        let timeout = (timeout.as_millis() / 100).min(u8::MAX as u128) as u8;
+        RawMode { timeout, ..self }
+    }
+
+    /// Applies the mode to the terminal `fd` refers to, until the returned guard is dropped. For
+    /// separate input and output terminals, apply the mode to each.
+    pub fn apply(self, fd: impl AsFd) -> io::Result<RawModeGuard> {
+        let fd = fd.as_fd().try_clone_to_owned()?;
+        let original = safe_tcgetattr(fd.as_raw_fd())?;
+        let mut configured = original;
+        self.configure(&mut configured);
+        safe_tcsetattr(fd.as_raw_fd(), &configured)?;
+
+        Ok(RawModeGuard {
+            fd,
+            original,
+            configured,
+        })
+    }
+
+    fn configure(&self, term: &mut termios) {
+        set_flag(&mut term.c_lflag, ECHO, self.echo);
+        if !self.echo {
+            term.c_lflag &= !ECHONL;
+        }
+        set_flag(&mut term.c_lflag, ICANON, self.canonical);
+        set_flag(&mut term.c_lflag, ISIG, self.signals);
+        set_flag(&mut term.c_iflag, IXON, self.flow_control);
+        term.c_cc[VMIN] = self.min_bytes;
+        term.c_cc[VTIME] = self.timeout;
+    }
+}
+
+/// Keeps a terminal in a [`RawMode`], restoring its previous settings when dropped. Only on Unix.
+pub struct RawModeGuard {
+    /// A duplicate of the descriptor the mode was applied to, so that the terminal can be
+    /// restored whatever happens to the original
+    fd: OwnedFd,
+    original: termios,
+    configured: termios,
+}
+
+impl RawModeGuard {
+    /// Puts the terminal back the way it was, until [`RawModeGuard::reapply`] is called.
+    pub fn restore(&self) -> io::Result<()> {
+        safe_tcsetattr(self.fd.as_raw_fd(), &self.original)
+    }
+
+    /// Applies the mode again, e.g. after [`RawModeGuard::restore`] or once the program is
+    /// resumed after being stopped, as the shell may have changed the terminal meanwhile.
+    pub fn reapply(&self) -> io::Result<()> {
+        safe_tcsetattr(self.fd.as_raw_fd(), &self.configured)
+    }
+
+    pub(crate) fn raw_fd(&self) -> c_int {
+        self.fd.as_raw_fd()
+    }
+
+    /// Returns the settings the terminal had before the mode was applied
+    pub(crate) fn original(&self) -> termios {
+        self.original
+    }
+
+    pub(crate) fn configured(&self) -> termios {
+        self.configured
+    }
+}
+
+impl std::fmt::Debug for RawModeGuard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RawModeGuard")
+            .field("fd", &self.fd)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for RawModeGuard {
+    fn drop(&mut self) {
+        let _ = self.restore();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::raw_mode::RawMode;
+    use libc::{ECHO, ICANON, ISIG, IXON, O_NOCTTY, O_RDWR, VMIN, VTIME};
+    use std::ffi::CStr;
+    use std::fs::{File, OpenOptions};
+    use std::os::fd::{AsRawFd, FromRawFd};
+    use std::time::Duration;
+
+    #[test]
+    fn raw_mode_guard() {
+        let (_master, path) = unsafe {
+            let master = libc::posix_openpt(O_RDWR | O_NOCTTY);
+            assert!(master >= 0);
+            assert_eq!(libc::grantpt(master), 0);
+            assert_eq!(libc::unlockpt(master), 0);
+            let path = CStr::from_ptr(libc::ptsname(master)).to_str().unwrap();
+            (File::from_raw_fd(master), path.to_string())
+        };
+        let slave = OpenOptions::new().read(true).open(&path).unwrap();
+        let fd = slave.as_raw_fd();
+        let original = super::safe_tcgetattr(fd).unwrap();
+
+        let guard = RawMode::new()
+            .signals(true)
+            .min_bytes(0)
+            .timeout(Duration::from_millis(1500))
+            .apply(&slave)
+            .unwrap();
+        let term = super::safe_tcgetattr(fd).unwrap();
+        assert_eq!(term.c_lflag & (ECHO | ICANON | ISIG), ISIG);
+        assert_eq!(term.c_iflag & IXON, 0);
+        assert_eq!((term.c_cc[VMIN], term.c_cc[VTIME]), (0, 15));
+
+        guard.restore().unwrap();
+        assert_eq!(super::safe_tcgetattr(fd).unwrap().c_lflag, original.c_lflag);
+        guard.reapply().unwrap();
+        assert_eq!(super::safe_tcgetattr(fd).unwrap().c_lflag, term.c_lflag);
+
+        // The guard keeps its own descriptor, so the terminal is restored even if ours is closed
+        guard.reapply().unwrap();
+        drop(slave);
+        let slave = OpenOptions::new().read(true).open(&path).unwrap();
+        drop(guard);
+        let term = super::safe_tcgetattr(slave.as_raw_fd()).unwrap();
+        assert_eq!(term.c_lflag, original.c_lflag);
+    }
+}
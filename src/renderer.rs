@@ -0,0 +1,402 @@
+use crate::config::PasswordFeedback;
+use std::hash::{BuildHasher, RandomState};
+use std::time::Duration;
+use unicode_width::UnicodeWidthStr;
+
+/// Number of masks `PasswordFeedback::Indicator` bounces between
+const INDICATOR_WIDTH: usize = 3;
+
+/// Something that happened to the password, as told to a [`FeedbackRenderer`].
+///
+/// Positions and lengths count chars as users see them, i.e. grapheme clusters: an accented
+/// letter or an emoji made of several code points is a single char. Events never carry what was
+/// typed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FeedbackEvent {
+    /// A key was pressed, before it is applied to the password
+    KeyPressed,
+    /// `count` chars were inserted at `position`, all at once if they were `pasted`
+    Inserted {
+        position: usize,
+        count: usize,
+        pasted: bool,
+    },
+    /// The chars from `start` up to `end` were deleted
+    Deleted { start: usize, end: usize },
+    /// Everything is drawn again from scratch, e.g. after the password was erased (Ctrl-U) or
+    /// the screen cleared (Ctrl-L), or when the renderer is installed, so any state kept about
+    /// the chars should be rebuilt for the `len` chars there are now
+    Cleared,
+    /// The delay returned by [`FeedbackRenderer::timeout`] has passed without any other event
+    TimedOut,
+    /// The password was submitted
+    Finished,
+    /// Typing was aborted and the password thrown away
+    Aborted,
+}
+
+/// How a single char of the password is drawn.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CharFeedback {
+    /// Nothing is drawn
+    Hidden,
+    /// The char itself is drawn
+    Plaintext,
+    /// The given text is drawn instead of the char, e.g. `*`
+    Mask(String),
+}
+
+/// Decides what is drawn while the password is typed.
+///
+/// Renderers are told about every edit along with the new length of the password, never about its
+/// contents. They either draw each char, see [`FeedbackRenderer::render_char`], or a status in
+/// place of the whole password, see [`FeedbackRenderer::render_status`]. The built-in
+/// [`PasswordFeedback`] modes are renderers too.
+///
+/// # Examples
+/// ```
+/// use rpassword::{CharFeedback, FeedbackEvent, FeedbackRenderer, Key, PasswordEditor};
+///
+/// /// Shows a dot for every char but the last one, which is shown as a star
+/// struct LastStar;
+///
+/// impl FeedbackRenderer for LastStar {
+///     fn handle_event(&mut self, _event: FeedbackEvent, _len: usize) {}
+///
+///     fn render_char(&self, position: usize, len: usize) -> CharFeedback {
+///         if position + 1 == len {
+///             CharFeedback::Mask("*".to_string())
+///         } else {
+///             CharFeedback::Mask(".".to_string())
+///         }
+///     }
+/// }
+///
+/// let mut editor = PasswordEditor::with_renderer(LastStar);
+/// editor.handle_key(Key::Char('a'));
+/// editor.handle_key(Key::Char('b'));
+/// assert_eq!(editor.display(), ".*");
+/// ```
+pub trait FeedbackRenderer: Send {
+    /// Updates the renderer after an event, `len` being the length of the password in chars
+    /// once the event happened.
+    fn handle_event(&mut self, event: FeedbackEvent, len: usize);
+
+    /// Returns how the char at `position` is drawn, when there is no status.
+    fn render_char(&self, position: usize, len: usize) -> CharFeedback;
+
+    /// Returns a status to draw in place of the password, e.g. how many chars were typed.
+    fn render_status(&self, _len: usize) -> Option<String> {
+        None
+    }
+
+    /// Returns after how long without any other event [`FeedbackEvent::TimedOut`] should be
+    /// sent, if ever.
+    fn timeout(&self) -> Option<Duration> {
+        None
+    }
+}
+
+/// Returns the renderer for one of the built-in feedback modes
+pub(crate) fn renderer(feedback: PasswordFeedback) -> Box<dyn FeedbackRenderer> {
+    renderer_with_mask(feedback, None)
+}
+
+/// Returns the renderer for one of the built-in feedback modes, drawing the given mask instead of
+/// the char of the feedback, if any
+pub(crate) fn renderer_with_mask(
+    feedback: PasswordFeedback,
+    mask: Option<String>,
+) -> Box<dyn FeedbackRenderer> {
+    let mask_or = |c: char| mask.clone().unwrap_or_else(|| c.to_string());
+    match feedback {
+        PasswordFeedback::Hide => Box::new(HideRenderer),
+        PasswordFeedback::Mask(c) => Box::new(PartialMaskRenderer {
+            mask: mask_or(c),
+            plaintext_count: 0,
+        }),
+        PasswordFeedback::PartialMask(c, plaintext_count) => Box::new(PartialMaskRenderer {
+            mask: mask_or(c),
+            plaintext_count,
+        }),
+        PasswordFeedback::FlashLast(c, timeout) => Box::new(FlashLastRenderer {
+            mask: mask_or(c),
+            timeout,
+            flashed: None,
+        }),
+        PasswordFeedback::RandomMask(c) => Box::new(RandomMaskRenderer {
+            mask: mask_or(c),
+            widths: Vec::new(),
+            // Xorshift gets stuck on 0, so make sure the seed is odd
+            random_state: RandomState::new().hash_one(0) | 1,
+        }),
+        PasswordFeedback::Indicator(c) => Box::new(IndicatorRenderer {
+            mask: mask_or(c),
+            frame: 0,
+        }),
+        PasswordFeedback::Counter(max) => Box::new(CounterRenderer { max }),
+    }
+}
+
+struct HideRenderer;
+
+impl FeedbackRenderer for HideRenderer {
+    fn handle_event(&mut self, _event: FeedbackEvent, _len: usize) {}
+
+    fn render_char(&self, _position: usize, _len: usize) -> CharFeedback {
+        CharFeedback::Hidden
+    }
+}
+
+/// Shows the first chars in plaintext and masks the others, `PasswordFeedback::Mask` being the
+/// case where no char is shown
+struct PartialMaskRenderer {
+    mask: String,
+    plaintext_count: usize,
+}
+
+impl FeedbackRenderer for PartialMaskRenderer {
+    fn handle_event(&mut self, _event: FeedbackEvent, _len: usize) {}
+
+    fn render_char(&self, position: usize, _len: usize) -> CharFeedback {
+        if position < self.plaintext_count {
+            CharFeedback::Plaintext
+        } else {
+            CharFeedback::Mask(self.mask.clone())
+        }
+    }
+}
+
+struct FlashLastRenderer {
+    mask: String,
+    timeout: Duration,
+    /// Position of the char shown in plaintext
+    flashed: Option<usize>,
+}
+
+impl FeedbackRenderer for FlashLastRenderer {
+    fn handle_event(&mut self, event: FeedbackEvent, _len: usize) {
+        self.flashed = match event {
+            // Pasted text is never shown
+            FeedbackEvent::Inserted {
+                position,
+                count: 1,
+                pasted: false,
+            } => Some(position),
+            _ => None,
+        };
+    }
+
+    fn render_char(&self, position: usize, _len: usize) -> CharFeedback {
+        if self.flashed == Some(position) {
+            CharFeedback::Plaintext
+        } else {
+            CharFeedback::Mask(self.mask.clone())
+        }
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        self.flashed.map(|_| self.timeout)
+    }
+}
+
+struct RandomMaskRenderer {
+    mask: String,
+    /// Number of mask chars drawn for each char
+    widths: Vec<usize>,
+    /// State of the xorshift generator picking the widths
+    random_state: u64,
+}
+
+impl RandomMaskRenderer {
+    /// Picks between 1 and 3 mask chars, with xorshift as it only needs to look random
+    fn random_width(&mut self) -> usize {
+        self.random_state ^= self.random_state << 13;
+        self.random_state ^= self.random_state >> 7;
+        self.random_state ^= self.random_state << 17;
+        (self.random_state % 3) as usize + 1
+    }
+}
+
+impl FeedbackRenderer for RandomMaskRenderer {
+    fn handle_event(&mut self, event: FeedbackEvent, len: usize) {
+        match event {
+            FeedbackEvent::Inserted {
+                position, count, ..
+            } => {
+                // Chars the widths are missing for are drawn with a single mask, so the ranges are
+                // clamped rather than trusted to match
+                let position = position.min(self.widths.len());
+                let widths: Vec<usize> = (0..count).map(|_| self.random_width()).collect();
+                self.widths.splice(position..position, widths);
+            }
+            FeedbackEvent::Deleted { start, end } => {
+                let end = end.min(self.widths.len());
+                self.widths.drain(start.min(end)..end);
+            }
+            FeedbackEvent::Cleared => {
+                self.widths = (0..len).map(|_| self.random_width()).collect();
+            }
+            FeedbackEvent::Aborted => self.widths.clear(),
+            _ => {}
+        }
+    }
+
+    fn render_char(&self, position: usize, _len: usize) -> CharFeedback {
+        let width = self.widths.get(position).copied().unwrap_or(1);
+        CharFeedback::Mask(self.mask.repeat(width))
+    }
+}
+
+/// Shows a mask char bouncing between a few cells, moving on every key
+struct IndicatorRenderer {
+    mask: String,
+    frame: usize,
+}
+
+impl FeedbackRenderer for IndicatorRenderer {
+    fn handle_event(&mut self, event: FeedbackEvent, _len: usize) {
+        if event == FeedbackEvent::KeyPressed {
+            self.frame = self.frame.wrapping_add(1);
+        }
+    }
+
+    fn render_char(&self, _position: usize, _len: usize) -> CharFeedback {
+        CharFeedback::Hidden
+    }
+
+    fn render_status(&self, len: usize) -> Option<String> {
+        if len == 0 {
+            return Some(String::new());
+        }
+
+        let period = 2 * (INDICATOR_WIDTH - 1);
+        let step = self.frame % period;
+        let lit = if step < INDICATOR_WIDTH {
+            step
+        } else {
+            period - step
+        };
+        let blank = " ".repeat(self.mask.width());
+        Some(
+            (0..INDICATOR_WIDTH)
+                .map(|cell| if cell == lit { &self.mask } else { &blank })
+                .map(String::as_str)
+                .collect(),
+        )
+    }
+}
+
+/// Shows the number of chars typed, along with the limit once the password gets within a tenth
+/// of it
+struct CounterRenderer {
+    max: Option<usize>,
+}
+
+impl FeedbackRenderer for CounterRenderer {
+    fn handle_event(&mut self, _event: FeedbackEvent, _len: usize) {}
+
+    fn render_char(&self, _position: usize, _len: usize) -> CharFeedback {
+        CharFeedback::Hidden
+    }
+
+    fn render_status(&self, len: usize) -> Option<String> {
+        if len == 0 {
+            return Some(String::new());
+        }
+
+        let noun = if len == 1 { "character" } else { "characters" };
+        Some(match self.max {
+            Some(max) if len * 10 >= max * 9 => format!("({len} {noun}, max {max})"),
+            _ => format!("({len} {noun})"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::editor::PasswordEditor;
+    use crate::key::Key;
+    use crate::renderer::{CharFeedback, FeedbackEvent, FeedbackRenderer};
+    use std::sync::{Arc, Mutex};
+
+    /// Masks every char with its position, and keeps the events it was told about
+    struct Recorder(Arc<Mutex<Vec<(FeedbackEvent, usize)>>>);
+
+    impl FeedbackRenderer for Recorder {
+        fn handle_event(&mut self, event: FeedbackEvent, len: usize) {
+            self.0.lock().unwrap().push((event, len));
+        }
+
+        fn render_char(&self, position: usize, _len: usize) -> CharFeedback {
+            CharFeedback::Mask(position.to_string())
+        }
+    }
+
+    #[test]
+    fn renderer_receives_events() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut editor = PasswordEditor::with_renderer(Recorder(events.clone()));
+        assert_eq!(editor.handle_key(Key::Char('a')).output, "0");
+        assert_eq!(editor.handle_key(Key::Paste("bc".to_string())).output, "12");
+        assert_eq!(editor.handle_key(Key::Home).output, "\x08\x08\x08");
+        assert_eq!(editor.handle_key(Key::Delete).output, "01 \x08\x08\x08");
+        assert_eq!(editor.display(), "01");
+        editor.handle_key(Key::Enter);
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                (FeedbackEvent::KeyPressed, 0),
+                (
+                    FeedbackEvent::Inserted {
+                        position: 0,
+                        count: 1,
+                        pasted: false
+                    },
+                    1
+                ),
+                (FeedbackEvent::KeyPressed, 1),
+                (
+                    FeedbackEvent::Inserted {
+                        position: 1,
+                        count: 2,
+                        pasted: true
+                    },
+                    3
+                ),
+                (FeedbackEvent::KeyPressed, 3),
+                (FeedbackEvent::KeyPressed, 3),
+                (FeedbackEvent::Deleted { start: 0, end: 1 }, 2),
+                (FeedbackEvent::KeyPressed, 2),
+                (FeedbackEvent::Finished, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn renderer_is_told_when_everything_is_redrawn() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut editor = PasswordEditor::with_renderer(Recorder(events.clone()));
+        editor.handle_key(Key::Paste("ab".to_string()));
+        events.lock().unwrap().clear();
+
+        assert_eq!(editor.handle_key(Key::Ctrl('l')).output, "\x1B[H\x1B[2J01");
+        assert_eq!(
+            editor.handle_key(Key::Ctrl('u')).output,
+            "\x08 \x08\x08 \x08"
+        );
+        assert!(editor.is_empty());
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                (FeedbackEvent::KeyPressed, 2),
+                (FeedbackEvent::Cleared, 2),
+                (FeedbackEvent::KeyPressed, 2),
+                (FeedbackEvent::Cleared, 0),
+            ]
+        );
+    }
+}
@@ -0,0 +1,278 @@
+//! Restores the terminal when the program ends without unwinding, i.e. on termination signals
+//! and on panics with `panic = "abort"`, see [`crate::ConfigBuilder::restore_terminal_on_exit`].
+//!
+//! Signal handlers may only touch memory that is ready before they run, so the termios are kept
+//! in static slots that are filled before being published through their file descriptor.
+
+use libc::{TCSANOW, c_int, c_void, sigaction, siginfo_t, tcsetattr, termios};
+use std::cell::UnsafeCell;
+use std::io;
+use std::mem::{self, MaybeUninit};
+use std::sync::Once;
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+
+/// Signals that end the program by default, SIGINT included as Ctrl-C raises it
+const RESTORED_SIGNALS: [c_int; 4] = [libc::SIGTERM, libc::SIGHUP, libc::SIGQUIT, libc::SIGINT];
+
+/// A terminal configured for reading, with its termios before and after
+struct Slot {
+    /// -1 while the termios are not ready to be read
+    fd: AtomicI32,
+    original: UnsafeCell<MaybeUninit<termios>>,
+    configured: UnsafeCell<MaybeUninit<termios>>,
+}
+
+// Termios are only written while `fd` is -1, and only read once it is set
+unsafe impl Sync for Slot {}
+
+impl Slot {
+    const fn new() -> Slot {
+        Slot {
+            fd: AtomicI32::new(-1),
+            original: UnsafeCell::new(MaybeUninit::uninit()),
+            configured: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+}
+
+/// Actions installed before ours, which are called after restoring the terminal
+struct PreviousActions(UnsafeCell<[MaybeUninit<sigaction>; RESTORED_SIGNALS.len()]>);
+
+// Written before our handlers are installed, only read by them afterwards
+unsafe impl Sync for PreviousActions {}
+
+/// The input and the output terminals
+static SLOTS: [Slot; 2] = [Slot::new(), Slot::new()];
+static PREVIOUS_ACTIONS: PreviousActions = PreviousActions(UnsafeCell::new(
+    [MaybeUninit::uninit(); RESTORED_SIGNALS.len()],
+));
+/// Whether a read is guarded, only one being at a time
+static ARMED: AtomicBool = AtomicBool::new(false);
+static PANIC_HOOK: Once = Once::new();
+
+/// Puts the terminals back as they were before reading, with async-signal-safe calls only
+fn restore_original() {
+    for slot in &SLOTS {
+        let fd = slot.fd.load(Ordering::Acquire);
+        if fd >= 0 {
+            unsafe {
+                tcsetattr(fd, TCSANOW, (*slot.original.get()).as_ptr());
+            }
+        }
+    }
+}
+
+/// Configures the terminals for reading again, once a previous handler let the program go on
+fn restore_configured() {
+    for slot in &SLOTS {
+        let fd = slot.fd.load(Ordering::Acquire);
+        if fd >= 0 {
+            unsafe {
+                tcsetattr(fd, TCSANOW, (*slot.configured.get()).as_ptr());
+            }
+        }
+    }
+}
+
+extern "C" fn restore_and_reraise(signal: c_int, info: *mut siginfo_t, context: *mut c_void) {
+    restore_original();
+
+    let Some(index) = RESTORED_SIGNALS.iter().position(|&s| s == signal) else {
+        return;
+    };
+    let previous = unsafe { (*PREVIOUS_ACTIONS.0.get())[index].assume_init_ref() };
+    match previous.sa_sigaction {
+        libc::SIG_DFL => unsafe {
+            // The signal is blocked until this handler returns, which is when the program ends
+            libc::sigaction(signal, previous, std::ptr::null_mut());
+            libc::raise(signal);
+        },
+        libc::SIG_IGN => restore_configured(),
+        _ => {
+            unsafe { call_handler(previous, signal, info, context) };
+            restore_configured();
+        }
+    }
+}
+
+/// Calls the handler of `action`, which is neither `SIG_DFL` nor `SIG_IGN`, as the kernel would
+///
+/// # Safety
+///
+/// `info` and `context` must be valid for handlers installed with `SA_SIGINFO`.
+pub(crate) unsafe fn call_handler(
+    action: &sigaction,
+    signal: c_int,
+    info: *mut siginfo_t,
+    context: *mut c_void,
+) {
+    if action.sa_flags & libc::SA_SIGINFO != 0 {
+        let handler: extern "C" fn(c_int, *mut siginfo_t, *mut c_void) =
+            unsafe { mem::transmute(action.sa_sigaction) };
+        handler(signal, info, context);
+    } else {
+        let handler: extern "C" fn(c_int) = unsafe { mem::transmute(action.sa_sigaction) };
+        handler(signal);
+    }
+}
+
+/// Keeps the terminals restorable from signal handlers and panic hooks until dropped
+pub(crate) struct RestoreGuard {
+    installed: usize,
+}
+
+impl RestoreGuard {
+    /// Records the terminals, given as their fd and their termios before and after being
+    /// configured, and installs the handlers. Returns `None` if another read is already guarded.
+    pub(crate) fn arm(terminals: &[(c_int, termios, termios)]) -> io::Result<Option<RestoreGuard>> {
+        if ARMED.swap(true, Ordering::AcqRel) {
+            return Ok(None);
+        }
+
+        for (slot, &(fd, original, configured)) in SLOTS.iter().zip(terminals) {
+            unsafe {
+                (*slot.original.get()).write(original);
+                (*slot.configured.get()).write(configured);
+            }
+            slot.fd.store(fd, Ordering::Release);
+        }
+
+        PANIC_HOOK.call_once(|| {
+            let previous_hook = std::panic::take_hook();
+            std::panic::set_hook(Box::new(move |info| {
+                if ARMED.load(Ordering::Acquire) {
+                    restore_original();
+                }
+                previous_hook(info);
+            }));
+        });
+
+        // Dropping disarms and restores the handlers installed so far if one fails
+        let mut guard = RestoreGuard { installed: 0 };
+        let mut action: sigaction = unsafe { mem::zeroed() };
+        action.sa_sigaction = restore_and_reraise
+            as extern "C" fn(c_int, *mut siginfo_t, *mut c_void)
+            as libc::sighandler_t;
+        action.sa_flags = libc::SA_SIGINFO | libc::SA_RESTART;
+        unsafe {
+            libc::sigemptyset(&mut action.sa_mask);
+        }
+        for (index, &signal) in RESTORED_SIGNALS.iter().enumerate() {
+            let previous = unsafe { &mut (*PREVIOUS_ACTIONS.0.get())[index] };
+            let result =
+                unsafe { libc::sigaction(signal, std::ptr::null(), previous.as_mut_ptr()) };
+            if result != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            if unsafe { previous.assume_init_ref() }.sa_sigaction == libc::SIG_IGN {
+                // Ignored signals do not end the program, so there is nothing to restore
+                guard.installed += 1;
+                continue;
+            }
+            if unsafe { libc::sigaction(signal, &action, std::ptr::null_mut()) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            guard.installed += 1;
+        }
+
+        Ok(Some(guard))
+    }
+}
+
+impl Drop for RestoreGuard {
+    fn drop(&mut self) {
+        for (index, &signal) in RESTORED_SIGNALS.iter().enumerate().take(self.installed) {
+            unsafe {
+                libc::sigaction(
+                    signal,
+                    (*PREVIOUS_ACTIONS.0.get())[index].as_ptr(),
+                    std::ptr::null_mut(),
+                );
+            }
+        }
+        for slot in &SLOTS {
+            slot.fd.store(-1, Ordering::Release);
+        }
+        ARMED.store(false, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RestoreGuard;
+    use libc::{ECHO, O_NOCTTY, O_RDWR, c_int};
+    use std::ffi::CStr;
+    use std::fs::{File, OpenOptions};
+    use std::mem;
+    use std::os::fd::{AsRawFd, FromRawFd};
+    use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+
+    static TERMINAL_FD: AtomicI32 = AtomicI32::new(-1);
+    static ECHO_IN_HANDLER: AtomicBool = AtomicBool::new(false);
+
+    extern "C" fn record_echo(_signal: c_int) {
+        let mut term: libc::termios = unsafe { mem::zeroed() };
+        unsafe {
+            libc::tcgetattr(TERMINAL_FD.load(Ordering::Relaxed), &mut term);
+        }
+        ECHO_IN_HANDLER.store(term.c_lflag & ECHO != 0, Ordering::Relaxed);
+    }
+
+    fn echo(fd: c_int) -> bool {
+        let mut term: libc::termios = unsafe { mem::zeroed() };
+        assert_eq!(unsafe { libc::tcgetattr(fd, &mut term) }, 0);
+        term.c_lflag & ECHO != 0
+    }
+
+    #[test]
+    fn restore_chains_previous_handler() {
+        let (_master, slave) = unsafe {
+            let master = libc::posix_openpt(O_RDWR | O_NOCTTY);
+            assert!(master >= 0);
+            assert_eq!(libc::grantpt(master), 0);
+            assert_eq!(libc::unlockpt(master), 0);
+            let path = CStr::from_ptr(libc::ptsname(master)).to_str().unwrap();
+            let slave = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(path)
+                .unwrap();
+            (File::from_raw_fd(master), slave)
+        };
+        let fd = slave.as_raw_fd();
+        TERMINAL_FD.store(fd, Ordering::Relaxed);
+
+        let mut original: libc::termios = unsafe { mem::zeroed() };
+        assert_eq!(unsafe { libc::tcgetattr(fd, &mut original) }, 0);
+        let mut configured = original;
+        configured.c_lflag &= !ECHO;
+        assert_eq!(
+            unsafe { libc::tcsetattr(fd, libc::TCSANOW, &configured) },
+            0
+        );
+
+        let mut handler: libc::sigaction = unsafe { mem::zeroed() };
+        handler.sa_sigaction = record_echo as extern "C" fn(c_int) as libc::sighandler_t;
+        let mut previous: libc::sigaction = unsafe { mem::zeroed() };
+        assert_eq!(
+            unsafe { libc::sigaction(libc::SIGHUP, &handler, &mut previous) },
+            0
+        );
+
+        let guard = RestoreGuard::arm(&[(fd, original, configured)]).unwrap();
+        assert!(guard.is_some());
+        unsafe {
+            libc::raise(libc::SIGHUP);
+        }
+        // The previous handler runs with the terminal restored, which is configured again after
+        assert!(ECHO_IN_HANDLER.load(Ordering::Relaxed));
+        assert!(!echo(fd));
+        drop(guard);
+
+        let mut current: libc::sigaction = unsafe { mem::zeroed() };
+        unsafe {
+            libc::sigaction(libc::SIGHUP, &previous, &mut current);
+        }
+        assert_eq!(current.sa_sigaction, handler.sa_sigaction);
+    }
+}
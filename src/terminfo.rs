@@ -0,0 +1,438 @@
+use std::path::PathBuf;
+
+/// Magic number of compiled terminfo entries storing numbers on 16 bits
+const MAGIC_16_BIT: i16 = 0o432;
+/// Magic number of compiled terminfo entries storing numbers on 32 bits, since ncurses 6.1
+const MAGIC_32_BIT: i16 = 0o1036;
+
+/// Indexes of the capabilities we use, in the order terminfo stores them
+const BOOL_HARD_COPY: usize = 7;
+const NUM_COLORS: usize = 13;
+const STR_CARRIAGE_RETURN: usize = 2;
+const STR_CLR_EOL: usize = 6;
+const STR_CURSOR_LEFT: usize = 14;
+const STR_PARM_UP_CURSOR: usize = 114;
+
+/// What the terminal supports, as far as drawing the feedback is concerned
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Capabilities {
+    /// Moves the cursor one cell to the left (`cub1`)
+    pub(crate) cursor_left: Option<String>,
+    /// Moves the cursor to the start of the line (`cr`)
+    pub(crate) carriage_return: Option<String>,
+    /// Erases from the cursor to the end of the line (`el`)
+    pub(crate) clear_to_end: Option<String>,
+    /// Whether ANSI sequences move the cursor up and to a column, and clear the screen
+    pub(crate) cursor_movement: bool,
+    pub(crate) bracketed_paste: bool,
+    pub(crate) colors: bool,
+}
+
+impl Capabilities {
+    /// Capabilities of the terminal emulators in use today, which understand ANSI sequences
+    pub(crate) fn ansi() -> Capabilities {
+        Capabilities {
+            cursor_left: Some("\x08".to_string()),
+            carriage_return: Some("\r".to_string()),
+            clear_to_end: Some("\x1B[K".to_string()),
+            cursor_movement: true,
+            bracketed_paste: true,
+            colors: true,
+        }
+    }
+
+    /// Capabilities of the Windows console, which is written to without virtual terminal
+    /// processing, so without escape sequences
+    #[cfg_attr(not(target_family = "windows"), allow(dead_code))]
+    pub(crate) fn console() -> Capabilities {
+        Capabilities {
+            clear_to_end: None,
+            cursor_movement: false,
+            bracketed_paste: false,
+            colors: false,
+            ..Capabilities::ansi()
+        }
+    }
+
+    /// Capabilities of a terminal that can only go back to the start of the line
+    fn dumb() -> Capabilities {
+        Capabilities {
+            cursor_left: None,
+            ..Capabilities::console()
+        }
+    }
+
+    /// Returns the capabilities of the terminal called `name`, as in `TERM`, from the terminfo
+    /// database if it has an entry for it, from a built-in table otherwise
+    #[cfg_attr(
+        not(all(target_family = "unix", not(target_family = "wasm"))),
+        allow(dead_code)
+    )]
+    pub(crate) fn for_terminal(name: Option<&str>) -> Capabilities {
+        let Some(name) = name.filter(|name| !name.is_empty()) else {
+            return Capabilities::ansi();
+        };
+
+        let builtin = Capabilities::builtin(name);
+        match read_entry(name).and_then(|entry| parse(&entry)) {
+            // Bracketed paste was only added to the database recently, older entries lack it
+            Some(capabilities) => Capabilities {
+                bracketed_paste: capabilities.bracketed_paste || builtin.bracketed_paste,
+                ..capabilities
+            },
+            None => builtin,
+        }
+    }
+
+    /// Returns the capabilities of well-known terminals, by the family of their name, assuming
+    /// ANSI sequences for the others
+    fn builtin(name: &str) -> Capabilities {
+        let family = name.split('-').next().unwrap_or_default();
+        match family {
+            "dumb" | "unknown" => Capabilities::dumb(),
+            "linux" | "ansi" | "cygwin" | "pcansi" => Capabilities {
+                bracketed_paste: false,
+                ..Capabilities::ansi()
+            },
+            "vt100" | "vt102" | "vt220" | "vt320" | "vt420" => Capabilities {
+                bracketed_paste: false,
+                colors: false,
+                ..Capabilities::ansi()
+            },
+            _ => Capabilities::ansi(),
+        }
+    }
+
+    /// Returns whether anything drawn can be erased, otherwise no feedback should be drawn
+    pub(crate) fn can_erase(&self) -> bool {
+        self.cursor_left.is_some() || self.carriage_return.is_some()
+    }
+}
+
+/// Returns the directories where terminfo entries are looked for, like ncurses does
+fn database_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Some(dir) = std::env::var_os("TERMINFO") {
+        dirs.push(PathBuf::from(dir));
+    }
+    if let Some(home) = std::env::var_os("HOME") {
+        dirs.push(PathBuf::from(home).join(".terminfo"));
+    }
+    if let Some(list) = std::env::var_os("TERMINFO_DIRS") {
+        // An empty item stands for the default location
+        for dir in std::env::split_paths(&list) {
+            if dir.as_os_str().is_empty() {
+                dirs.push(PathBuf::from("/usr/share/terminfo"));
+            } else {
+                dirs.push(dir);
+            }
+        }
+    }
+    for dir in [
+        "/etc/terminfo",
+        "/lib/terminfo",
+        "/usr/share/terminfo",
+        "/usr/lib/terminfo",
+    ] {
+        dirs.push(PathBuf::from(dir));
+    }
+    dirs
+}
+
+/// Reads the compiled entry of the terminal, stored either under its first letter, or under the
+/// hexadecimal code of that letter on macOS
+fn read_entry(name: &str) -> Option<Vec<u8>> {
+    if name.contains('/') || name.starts_with('.') {
+        return None;
+    }
+    let first = name.chars().next()?;
+
+    database_dirs().into_iter().find_map(|dir| {
+        [
+            dir.join(first.to_string()).join(name),
+            dir.join(format!("{:x}", first as u32)).join(name),
+        ]
+        .into_iter()
+        .find_map(|path| std::fs::read(path).ok())
+    })
+}
+
+/// Reads little-endian 16 bit integers from a compiled entry
+struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl Reader<'_> {
+    fn bytes(&mut self, len: usize) -> Option<&[u8]> {
+        let bytes = self.data.get(self.offset..self.offset.checked_add(len)?)?;
+        self.offset += len;
+        Some(bytes)
+    }
+
+    fn i16(&mut self) -> Option<i16> {
+        let bytes = self.bytes(2)?;
+        Some(i16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn count(&mut self) -> Option<usize> {
+        usize::try_from(self.i16()?).ok()
+    }
+
+    fn number(&mut self, size: usize) -> Option<i32> {
+        let bytes = self.bytes(size)?;
+        Some(match *bytes {
+            [a, b] => i16::from_le_bytes([a, b]) as i32,
+            [a, b, c, d] => i32::from_le_bytes([a, b, c, d]),
+            _ => return None,
+        })
+    }
+
+    /// Skips the padding byte that keeps what follows aligned on 16 bits
+    fn align(&mut self) {
+        self.offset += self.offset % 2;
+    }
+}
+
+/// Returns the NUL-terminated string at `offset` in the string table, if it is set
+fn string_at(table: &[u8], offset: i16) -> Option<String> {
+    let start = usize::try_from(offset).ok()?;
+    let bytes = table.get(start..)?;
+    let end = bytes.iter().position(|&byte| byte == 0)?;
+    Some(String::from_utf8_lossy(&bytes[..end]).into_owned())
+}
+
+/// Removes the delays from a capability, e.g. `$<3>`, which old terminals needed to keep up
+fn without_delays(value: &str) -> String {
+    let mut result = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find("$<") {
+        result.push_str(&rest[..start]);
+        match rest[start..].find('>') {
+            Some(end) => rest = &rest[start + end + 1..],
+            None => {
+                rest = &rest[start..];
+                break;
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Parses a compiled terminfo entry, see `term(5)`
+fn parse(data: &[u8]) -> Option<Capabilities> {
+    let mut reader = Reader { data, offset: 0 };
+    let number_size = match reader.i16()? {
+        MAGIC_16_BIT => 2,
+        MAGIC_32_BIT => 4,
+        _ => return None,
+    };
+    let names_size = reader.count()?;
+    let bool_count = reader.count()?;
+    let number_count = reader.count()?;
+    let string_count = reader.count()?;
+    let table_size = reader.count()?;
+
+    reader.bytes(names_size)?;
+    let bools = reader.bytes(bool_count)?.to_vec();
+    reader.align();
+    let numbers = (0..number_count)
+        .map(|_| reader.number(number_size))
+        .collect::<Option<Vec<i32>>>()?;
+    let offsets = (0..string_count)
+        .map(|_| reader.i16())
+        .collect::<Option<Vec<i16>>>()?;
+    let table = reader.bytes(table_size)?.to_vec();
+    let string = |index: usize| {
+        offsets
+            .get(index)
+            .and_then(|&offset| string_at(&table, offset))
+            .map(|value| without_delays(&value))
+    };
+
+    let hard_copy = bools.get(BOOL_HARD_COPY).is_some_and(|&value| value == 1);
+    let colors = numbers.get(NUM_COLORS).is_some_and(|&colors| colors >= 8);
+    let cursor_movement =
+        string(STR_PARM_UP_CURSOR).is_some_and(|cuu| cuu.starts_with("\x1B[%p1%d"));
+    if hard_copy {
+        // What is printed on paper cannot be erased
+        return Some(Capabilities {
+            carriage_return: None,
+            ..Capabilities::dumb()
+        });
+    }
+
+    reader.align();
+    Some(Capabilities {
+        cursor_left: string(STR_CURSOR_LEFT),
+        carriage_return: string(STR_CARRIAGE_RETURN),
+        clear_to_end: string(STR_CLR_EOL),
+        cursor_movement,
+        bracketed_paste: parse_extended(&mut reader, number_size)
+            .is_some_and(|names| names.iter().any(|name| name == "BE")),
+        colors,
+    })
+}
+
+/// Returns the names of the extended string capabilities that are set, e.g. `BE` which enables
+/// bracketed paste
+fn parse_extended(reader: &mut Reader, number_size: usize) -> Option<Vec<String>> {
+    let bool_count = reader.count()?;
+    let number_count = reader.count()?;
+    let string_count = reader.count()?;
+    // Number of strings in the table, values and names
+    reader.count()?;
+    let table_size = reader.count()?;
+
+    reader.bytes(bool_count)?;
+    reader.align();
+    reader.bytes(number_count * number_size)?;
+    let offsets = (0..string_count)
+        .map(|_| reader.i16())
+        .collect::<Option<Vec<i16>>>()?;
+    let name_offsets = (0..bool_count + number_count + string_count)
+        .map(|_| reader.i16())
+        .collect::<Option<Vec<i16>>>()?;
+    let table = reader.bytes(table_size)?;
+
+    // The names come after the values, their offsets starting where the values end
+    let values_end = offsets
+        .iter()
+        .filter_map(|&offset| {
+            Some(usize::try_from(offset).ok()? + string_at(table, offset)?.len() + 1)
+        })
+        .max()
+        .unwrap_or(0);
+    let names = table.get(values_end..)?;
+    Some(
+        offsets
+            .iter()
+            .zip(&name_offsets[bool_count + number_count..])
+            .filter(|&(&offset, _)| offset >= 0)
+            .filter_map(|(_, &name_offset)| string_at(names, name_offset))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::terminfo::{Capabilities, MAGIC_16_BIT, parse, without_delays};
+
+    /// Compiles an entry with the given strings, by index, and extended strings, by name
+    fn compile(colors: i16, strings: &[(usize, &str)], extended: &[(&str, &str)]) -> Vec<u8> {
+        fn push(data: &mut Vec<u8>, value: i16) {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+
+        let names = b"test|Test terminal\0";
+        let string_count = strings
+            .iter()
+            .map(|&(index, _)| index + 1)
+            .max()
+            .unwrap_or(0);
+        let mut offsets = vec![-1i16; string_count];
+        let mut table = Vec::new();
+        for &(index, value) in strings {
+            offsets[index] = table.len() as i16;
+            table.extend_from_slice(value.as_bytes());
+            table.push(0);
+        }
+
+        let mut data = Vec::new();
+        for value in [
+            MAGIC_16_BIT,
+            names.len() as i16,
+            8,
+            14,
+            string_count as i16,
+            table.len() as i16,
+        ] {
+            push(&mut data, value);
+        }
+        data.extend_from_slice(names);
+        data.extend_from_slice(&[0; 8]);
+        data.resize(data.len() + data.len() % 2, 0);
+        for index in 0..14 {
+            push(&mut data, if index == 13 { colors } else { -1 });
+        }
+        for offset in offsets {
+            push(&mut data, offset);
+        }
+        data.extend_from_slice(&table);
+        data.resize(data.len() + data.len() % 2, 0);
+
+        if extended.is_empty() {
+            return data;
+        }
+        let mut values = Vec::new();
+        let mut value_offsets = Vec::new();
+        for (_, value) in extended {
+            value_offsets.push(values.len() as i16);
+            values.extend_from_slice(value.as_bytes());
+            values.push(0);
+        }
+        let mut names = Vec::new();
+        let mut name_offsets = Vec::new();
+        for (name, _) in extended {
+            name_offsets.push(names.len() as i16);
+            names.extend_from_slice(name.as_bytes());
+            names.push(0);
+        }
+        let count = extended.len() as i16;
+        for value in [0, 0, count, 2 * count, (values.len() + names.len()) as i16] {
+            push(&mut data, value);
+        }
+        for offset in value_offsets.into_iter().chain(name_offsets) {
+            push(&mut data, offset);
+        }
+        data.extend_from_slice(&values);
+        data.extend_from_slice(&names);
+        data
+    }
+
+    #[test]
+    fn terminfo_parse() {
+        let entry = compile(
+            256,
+            &[
+                (2, "\r"),
+                (6, "\x1B[K$<3>"),
+                (14, "\x08"),
+                (114, "\x1B[%p1%dA"),
+            ],
+            &[("AX", ""), ("BE", "\x1B[?2004h")],
+        );
+        assert_eq!(parse(&entry), Some(Capabilities::ansi()));
+
+        let entry = compile(-1, &[(2, "\r"), (14, "\x1B[D")], &[]);
+        assert_eq!(
+            parse(&entry),
+            Some(Capabilities {
+                cursor_left: Some("\x1B[D".to_string()),
+                carriage_return: Some("\r".to_string()),
+                clear_to_end: None,
+                cursor_movement: false,
+                bracketed_paste: false,
+                colors: false,
+            })
+        );
+
+        assert_eq!(parse(b"not terminfo"), None);
+        assert_eq!(without_delays("a$<5*/>b$<2"), "ab$<2");
+        assert_eq!(parse(&entry[..20]), None);
+    }
+
+    #[test]
+    fn terminfo_builtin_table() {
+        assert_eq!(Capabilities::for_terminal(None), Capabilities::ansi());
+        assert_eq!(Capabilities::builtin("dumb").cursor_left, None);
+        assert!(Capabilities::builtin("dumb").can_erase());
+        assert!(!Capabilities::builtin("vt100").colors);
+        assert!(!Capabilities::builtin("linux").bracketed_paste);
+        assert_eq!(
+            Capabilities::builtin("xterm-256color"),
+            Capabilities::ansi()
+        );
+    }
+}
@@ -0,0 +1,232 @@
+/// One of the colours terminals support.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Color {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+    /// Bright black, which most terminals show as grey
+    Grey,
+    /// One of the 256 colours of the terminal palette
+    Ansi256(u8),
+}
+
+impl Color {
+    fn sgr(self) -> String {
+        match self {
+            Color::Black => "30".to_string(),
+            Color::Red => "31".to_string(),
+            Color::Green => "32".to_string(),
+            Color::Yellow => "33".to_string(),
+            Color::Blue => "34".to_string(),
+            Color::Magenta => "35".to_string(),
+            Color::Cyan => "36".to_string(),
+            Color::White => "37".to_string(),
+            Color::Grey => "90".to_string(),
+            Color::Ansi256(index) => format!("38;5;{index}"),
+        }
+    }
+}
+
+/// How a part of the prompt is drawn: its colour and weight.
+///
+/// # Examples
+/// ```
+/// use rpassword::{Color, Style};
+///
+/// let style = Style::new().fg(Color::Cyan).bold();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Style {
+    foreground: Option<Color>,
+    bold: bool,
+    dim: bool,
+}
+
+impl Style {
+    /// Creates a style drawing text as the terminal does by default.
+    pub fn new() -> Style {
+        Style::default()
+    }
+
+    /// Sets the colour of the text.
+    pub fn fg(self, color: Color) -> Style {
+        Style {
+            foreground: Some(color),
+            ..self
+        }
+    }
+
+    /// Draws the text in bold.
+    pub fn bold(self) -> Style {
+        Style { bold: true, ..self }
+    }
+
+    /// Draws the text dimmed, e.g. for hints.
+    pub fn dim(self) -> Style {
+        Style { dim: true, ..self }
+    }
+
+    /// Returns the escape sequence switching to this style, empty for the default style
+    pub(crate) fn start(&self) -> String {
+        let mut codes = Vec::new();
+        if self.bold {
+            codes.push("1".to_string());
+        }
+        if self.dim {
+            codes.push("2".to_string());
+        }
+        if let Some(color) = self.foreground {
+            codes.push(color.sgr());
+        }
+        if codes.is_empty() {
+            return String::new();
+        }
+        format!("\x1B[{}m", codes.join(";"))
+    }
+
+    /// Returns the escape sequence switching back to the default style, empty if this is the
+    /// default style
+    pub(crate) fn end(&self) -> &'static str {
+        if *self == Style::default() {
+            ""
+        } else {
+            "\x1B[0m"
+        }
+    }
+
+    /// Returns the text wrapped in the escape sequences drawing it with this style
+    pub(crate) fn paint(&self, text: &str) -> String {
+        if text.is_empty() {
+            return String::new();
+        }
+        format!("{}{text}{}", self.start(), self.end())
+    }
+}
+
+/// Styles the prompt and the feedback drawn while the password is typed.
+///
+/// Styles are left out when the `NO_COLOR` environment variable is set, when `TERM` is `dumb`, or
+/// when the output is not a terminal. Texts such as the mask and the hint are used regardless.
+///
+/// # Examples
+/// ```
+/// use rpassword::{Color, ConfigBuilder, Style, Theme};
+///
+/// let theme = Theme::new()
+///     .prompt_style(Style::new().fg(Color::Cyan).bold())
+///     .mask("•")
+///     .mask_style(Style::new().fg(Color::Grey))
+///     .hint("Ctrl-R to reveal")
+///     .hint_style(Style::new().dim());
+///
+/// let config = ConfigBuilder::new()
+///     .password_feedback_mask('*')
+///     .allow_reveal()
+///     .theme(theme)
+///     .build();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Theme {
+    pub(crate) prompt_style: Style,
+    pub(crate) mask: Option<String>,
+    pub(crate) mask_style: Style,
+    pub(crate) hint: Option<String>,
+    pub(crate) hint_style: Style,
+}
+
+impl Theme {
+    /// Creates a theme without any style, which is the default.
+    pub fn new() -> Theme {
+        Theme::default()
+    }
+
+    /// Sets the style of the prompt.
+    pub fn prompt_style(self, prompt_style: Style) -> Theme {
+        Theme {
+            prompt_style,
+            ..self
+        }
+    }
+
+    /// Replaces the mask char of the [`crate::PasswordFeedback`] with the given text, which may
+    /// be several chars long, e.g. `"•"` or `"**"`.
+    pub fn mask(self, mask: impl Into<String>) -> Theme {
+        Theme {
+            mask: Some(mask.into()),
+            ..self
+        }
+    }
+
+    /// Sets the style of the feedback, i.e. masks and statuses such as the counter, plaintext
+    /// chars being drawn without style.
+    pub fn mask_style(self, mask_style: Style) -> Theme {
+        Theme { mask_style, ..self }
+    }
+
+    /// Sets a hint drawn at the right end of the prompt line, e.g. `"Ctrl-R to reveal"`. It is
+    /// only drawn when the width of the terminal is known and the hint fits on the line.
+    pub fn hint(self, hint: impl Into<String>) -> Theme {
+        Theme {
+            hint: Some(hint.into()),
+            ..self
+        }
+    }
+
+    /// Sets the style of the hint.
+    pub fn hint_style(self, hint_style: Style) -> Theme {
+        Theme { hint_style, ..self }
+    }
+
+    /// Returns the theme without its styles, keeping its texts
+    pub(crate) fn without_styles(self) -> Theme {
+        Theme {
+            prompt_style: Style::default(),
+            mask_style: Style::default(),
+            hint_style: Style::default(),
+            ..self
+        }
+    }
+}
+
+/// Returns whether the environment allows styling the output, see <https://no-color.org>
+pub(crate) fn styling_allowed() -> bool {
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    let dumb = std::env::var_os("TERM").is_some_and(|term| term == "dumb");
+    !no_color && !dumb
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::theme::{Color, Style, Theme};
+
+    #[test]
+    fn style_paint() {
+        assert_eq!(Style::new().paint("a"), "a");
+        assert_eq!(Style::new().fg(Color::Grey).paint("•"), "\x1B[90m•\x1B[0m");
+        assert_eq!(
+            Style::new().bold().dim().fg(Color::Ansi256(208)).paint("a"),
+            "\x1B[1;2;38;5;208ma\x1B[0m"
+        );
+        assert_eq!(Style::new().bold().paint(""), "");
+    }
+
+    #[test]
+    fn theme_without_styles() {
+        let theme = Theme::new()
+            .prompt_style(Style::new().bold())
+            .mask("•")
+            .hint("hint")
+            .hint_style(Style::new().dim())
+            .without_styles();
+        assert_eq!(theme.prompt_style, Style::default());
+        assert_eq!(theme.hint_style, Style::default());
+        assert_eq!(theme.mask.as_deref(), Some("•"));
+        assert_eq!(theme.hint.as_deref(), Some("hint"));
+    }
+}
@@ -1,12 +1,23 @@
 use crate::RawPasswordInput;
-use crate::config::{Config, InputTarget, OutputTarget};
+use crate::config::{Config, InputTarget, OutputTarget, TerminalFallback};
+use crate::key::{CharSource, Key};
+use crate::keymap::Action;
+use crate::raw_mode::{RawMode, RawModeGuard};
+use crate::restore::{RestoreGuard, call_handler};
+use crate::terminfo::Capabilities;
 use crate::utf8::read_char;
-use libc::{ECHO, ECHONL, ICANON, ISIG, TCSANOW, VMIN, VTIME, c_int, isatty, tcsetattr, termios};
-use std::fs::OpenOptions;
+use libc::{TCOON, VEOF, VERASE, VINTR, VKILL, VLNEXT, VSUSP, VWERASE, c_int, isatty};
+use std::cell::UnsafeCell;
+use std::ffi::c_void;
+use std::ffi::{CStr, OsStr};
+use std::fs::{File, OpenOptions};
 use std::io::{self, Cursor, Read, Write};
-use std::mem;
-use std::os::fd::RawFd;
+use std::mem::{self, MaybeUninit};
+use std::os::fd::{BorrowedFd, RawFd};
+use std::os::unix::ffi::OsStrExt;
 use std::os::unix::io::AsRawFd;
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::time::Duration;
 
 pub(crate) const DEFAULT_INPUT_PATH: &str = "/dev/tty";
 pub(crate) const DEFAULT_OUTPUT_PATH: &str = "/dev/tty";
@@ -19,54 +30,350 @@ fn io_result(ret: c_int) -> std::io::Result<()> {
     }
 }
 
+/// Special characters set with `stty`, the action they trigger and their usual value
+const SPECIAL_CHARS: [(usize, Action, u8); 7] = [
+    (VERASE, Action::EraseChar, 0x7F),
+    (VKILL, Action::EraseLine, 0x15),
+    (VWERASE, Action::EraseWord, 0x17),
+    (VINTR, Action::Cancel, 0x03),
+    (VSUSP, Action::Suspend, 0x1A),
+    (VEOF, Action::EndOfFile, 0x04),
+    (VLNEXT, Action::LiteralNext, 0x16),
+];
+
+/// Write end of the pipe signals are reported to, -1 when no password is being read
+static SIGNAL_PIPE: AtomicI32 = AtomicI32::new(-1);
+/// Whether `SIGWINCH`, `SIGTSTP` and `SIGCONT` were received since they were last handled
+static RESIZED: AtomicBool = AtomicBool::new(false);
+static STOP_REQUESTED: AtomicBool = AtomicBool::new(false);
+static CONTINUED: AtomicBool = AtomicBool::new(false);
+
+/// Signals handled while reading a password
+const WATCHED_SIGNALS: [c_int; 3] = [libc::SIGWINCH, libc::SIGTSTP, libc::SIGCONT];
+
+/// Actions installed before the watcher, which are called after reporting the signal
+struct PreviousActions(UnsafeCell<[MaybeUninit<libc::sigaction>; WATCHED_SIGNALS.len()]>);
+
+// Written before the watcher's handlers are installed, only read by them afterwards
+unsafe impl Sync for PreviousActions {}
+
+static PREVIOUS_ACTIONS: PreviousActions = PreviousActions(UnsafeCell::new(
+    [MaybeUninit::uninit(); WATCHED_SIGNALS.len()],
+));
+
+const NOTIFY_SIGNAL: extern "C" fn(c_int, *mut libc::siginfo_t, *mut c_void) = notify_signal;
+
+/// Returns whether the action runs a handler of the program, rather than a default or ours
+fn is_foreign_handler(action: &libc::sigaction) -> bool {
+    !matches!(action.sa_sigaction, libc::SIG_DFL | libc::SIG_IGN)
+        && action.sa_sigaction != NOTIFY_SIGNAL as libc::sighandler_t
+}
+
+extern "C" fn notify_signal(signal: c_int, info: *mut libc::siginfo_t, context: *mut c_void) {
+    let flag = match signal {
+        libc::SIGWINCH => &RESIZED,
+        libc::SIGTSTP => &STOP_REQUESTED,
+        _ => &CONTINUED,
+    };
+    flag.store(true, Ordering::Relaxed);
+
+    let fd = SIGNAL_PIPE.load(Ordering::Relaxed);
+    if fd >= 0 {
+        // Only async-signal-safe calls are allowed here, and a full pipe already tells enough
+        unsafe {
+            libc::write(fd, [0u8].as_ptr().cast(), 1);
+        }
+    }
+
+    // The program's own handler for SIGTSTP is called by `stop`, once the terminal is restored
+    let Some(index) = WATCHED_SIGNALS.iter().position(|&s| s == signal) else {
+        return;
+    };
+    let previous = unsafe { (*PREVIOUS_ACTIONS.0.get())[index].assume_init_ref() };
+    if signal != libc::SIGTSTP && is_foreign_handler(previous) {
+        unsafe { call_handler(previous, signal, info, context) };
+    }
+}
+
+/// Reports `SIGWINCH`, `SIGTSTP` and `SIGCONT` to the read loop through a pipe, so that polling
+/// for keys wakes up when the terminal is resized or the program is stopped and continued.
+/// Handlers the program installed before are still called, and put back once dropped.
+struct SignalWatcher {
+    read_fd: RawFd,
+    write_fd: RawFd,
+    previous_actions: Vec<(c_int, libc::sigaction)>,
+}
+
+impl SignalWatcher {
+    fn install() -> io::Result<SignalWatcher> {
+        let mut fds = [0; 2];
+        io_result(unsafe { libc::pipe(fds.as_mut_ptr()) })?;
+        let [read_fd, write_fd] = fds;
+        for fd in fds {
+            unsafe {
+                libc::fcntl(fd, libc::F_SETFL, libc::O_NONBLOCK);
+                libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC);
+            }
+        }
+        SIGNAL_PIPE.store(write_fd, Ordering::Relaxed);
+        for flag in [&RESIZED, &STOP_REQUESTED, &CONTINUED] {
+            flag.store(false, Ordering::Relaxed);
+        }
+
+        // Dropping restores the actions installed so far if one fails
+        let mut watcher = SignalWatcher {
+            read_fd,
+            write_fd,
+            previous_actions: Vec::new(),
+        };
+        let mut action: libc::sigaction = unsafe { mem::zeroed() };
+        action.sa_sigaction = NOTIFY_SIGNAL as libc::sighandler_t;
+        action.sa_flags = libc::SA_SIGINFO | libc::SA_RESTART;
+        unsafe {
+            libc::sigemptyset(&mut action.sa_mask);
+        }
+        for (index, signal) in WATCHED_SIGNALS.into_iter().enumerate() {
+            let mut previous_action: libc::sigaction = unsafe { mem::zeroed() };
+            io_result(unsafe { libc::sigaction(signal, std::ptr::null(), &mut previous_action) })?;
+            if signal == libc::SIGTSTP && previous_action.sa_sigaction == libc::SIG_IGN {
+                // The program does not want to be stopped
+                continue;
+            }
+            // A watcher that is already installed keeps the program's actions where they are
+            if previous_action.sa_sigaction != NOTIFY_SIGNAL as libc::sighandler_t {
+                unsafe {
+                    (*PREVIOUS_ACTIONS.0.get())[index].write(previous_action);
+                }
+            }
+            io_result(unsafe { libc::sigaction(signal, &action, std::ptr::null_mut()) })?;
+            watcher.previous_actions.push((signal, previous_action));
+        }
+
+        Ok(watcher)
+    }
+
+    /// Returns whether the signal behind the flag was received since the last call
+    fn take(&self, flag: &AtomicBool) -> bool {
+        let mut buf = [0u8; 16];
+        while unsafe { libc::read(self.read_fd, buf.as_mut_ptr().cast(), buf.len()) } > 0 {}
+        flag.swap(false, Ordering::Relaxed)
+    }
+
+    /// Stops the program until it is continued, even though `SIGTSTP` is caught, or lets the
+    /// program's own `SIGTSTP` handler do it
+    fn stop(&self) -> io::Result<()> {
+        let previous = self
+            .previous_actions
+            .iter()
+            .find(|(signal, _)| *signal == libc::SIGTSTP)
+            .map(|(_, previous)| previous);
+        // Ours, put back afterwards
+        let mut action: libc::sigaction = unsafe { mem::zeroed() };
+        let result = match previous {
+            Some(previous) if is_foreign_handler(previous) => {
+                io_result(unsafe {
+                    libc::sigaction(libc::SIGTSTP, std::ptr::null(), &mut action)
+                })?;
+                let mut info: libc::siginfo_t = unsafe { mem::zeroed() };
+                info.si_signo = libc::SIGTSTP;
+                unsafe { call_handler(previous, libc::SIGTSTP, &mut info, std::ptr::null_mut()) };
+                Ok(())
+            }
+            _ => {
+                let mut default_action: libc::sigaction = unsafe { mem::zeroed() };
+                default_action.sa_sigaction = libc::SIG_DFL;
+                io_result(unsafe { libc::sigaction(libc::SIGTSTP, &default_action, &mut action) })?;
+                io_result(unsafe { libc::raise(libc::SIGTSTP) })
+            }
+        };
+        unsafe {
+            libc::sigaction(libc::SIGTSTP, &action, std::ptr::null_mut());
+        }
+        // The program is continued by now, which needs no handling of its own
+        self.take(&CONTINUED);
+        result
+    }
+}
+
+impl Drop for SignalWatcher {
+    fn drop(&mut self) {
+        for (signal, previous_action) in &self.previous_actions {
+            unsafe {
+                libc::sigaction(*signal, previous_action, std::ptr::null_mut());
+            }
+        }
+        // Another read may have started since, in which case the pipe is its own
+        let _ =
+            SIGNAL_PIPE.compare_exchange(self.write_fd, -1, Ordering::Relaxed, Ordering::Relaxed);
+        unsafe {
+            libc::close(self.read_fd);
+            libc::close(self.write_fd);
+        }
+    }
+}
+
+/// Waits up to `timeout`, or forever, until one of the file descriptors can be read
+fn poll(fds: &mut [libc::pollfd], timeout: Option<Duration>) -> io::Result<bool> {
+    let timeout = timeout.map_or(-1, |timeout| {
+        timeout.as_millis().min(c_int::MAX as u128) as c_int
+    });
+    loop {
+        match unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, timeout) } {
+            -1 => {
+                let err = std::io::Error::last_os_error();
+                if err.kind() != io::ErrorKind::Interrupted {
+                    return Err(err);
+                }
+            }
+            0 => return Ok(false),
+            _ => return Ok(true),
+        }
+    }
+}
+
 fn is_interactive_terminal(fd: c_int) -> bool {
     unsafe { isatty(fd) != 0 }
 }
 
-fn safe_tcgetattr(fd: c_int) -> std::io::Result<termios> {
-    let mut term = mem::MaybeUninit::<termios>::uninit();
-    io_result(unsafe { ::libc::tcgetattr(fd, term.as_mut_ptr()) })?;
-    Ok(unsafe { term.assume_init() })
+/// Standard streams whose terminal is used when there is no controlling terminal
+const STANDARD_STREAMS: [(c_int, &str); 3] = [
+    (libc::STDIN_FILENO, "stdin"),
+    (libc::STDERR_FILENO, "stderr"),
+    (libc::STDOUT_FILENO, "stdout"),
+];
+
+/// Returns the path of the terminal `fd` refers to
+fn ttyname(fd: c_int) -> io::Result<std::path::PathBuf> {
+    let mut name = [0 as libc::c_char; libc::PATH_MAX as usize];
+    match unsafe { libc::ttyname_r(fd, name.as_mut_ptr(), name.len()) } {
+        0 => {
+            let name = unsafe { CStr::from_ptr(name.as_ptr()) };
+            Ok(OsStr::from_bytes(name.to_bytes()).into())
+        }
+        err => Err(io::Error::from_raw_os_error(err)),
+    }
 }
 
-fn safe_tcsetattr(fd: c_int, term: &mut termios) -> std::io::Result<()> {
-    io_result(unsafe { tcsetattr(fd, TCSANOW, term) })
+/// Opens the controlling terminal at `path`, or else the terminal of the first of `streams` that
+/// is connected to one. Fails with every attempt in the error message.
+fn open_terminal(path: &str, options: &OpenOptions, streams: &[(c_int, &str)]) -> io::Result<File> {
+    let mut tried = Vec::new();
+    match options.open(path) {
+        Ok(file) => return Ok(file),
+        // ENXIO is a cryptic "No such device or address"
+        Err(err) if err.raw_os_error() == Some(libc::ENXIO) => {
+            tried.push(format!("{path}: the process has no controlling terminal"))
+        }
+        Err(err) => tried.push(format!("{path}: {err}")),
+    }
+    for &(fd, name) in streams {
+        if !is_interactive_terminal(fd) {
+            tried.push(format!("{name}: not a terminal"));
+            continue;
+        }
+        match ttyname(fd).and_then(|path| options.open(path)) {
+            Ok(file) => return Ok(file),
+            Err(err) => tried.push(format!("{name}: {err}")),
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        format!(
+            "no terminal to read the password from ({})",
+            tried.join(", ")
+        ),
+    ))
 }
 
 pub(crate) struct RawModeInput {
     input: Box<dyn Read>,
     input_fd: Option<RawFd>,
-    input_term_orig: Option<termios>,
     input_is_tty: bool,
+    input_guard: Option<RawModeGuard>,
     output: Box<dyn Write>,
     output_fd: Option<RawFd>,
-    output_term_orig: Option<termios>,
     output_is_tty: bool,
+    output_guard: Option<RawModeGuard>,
+    signal_watcher: Option<SignalWatcher>,
+    restore_on_exit: bool,
+    restore_guard: Option<RestoreGuard>,
 }
 
 impl Drop for RawModeInput {
     fn drop(&mut self) {
-        if let Some(fd) = self.input_fd
-            && let Some(ref mut term_orig) = self.input_term_orig
+        // The output first, see `restore_terminal_configuration`
+        self.output_guard.take();
+        self.input_guard.take();
+    }
+}
+
+impl RawModeInput {
+    /// Puts the terminals back the way they were before the password was read, the output first
+    /// as its original settings are the input's when both are the same terminal
+    fn restore_terminal_configuration(&self) {
+        for guard in [&self.output_guard, &self.input_guard]
+            .into_iter()
+            .flatten()
         {
-            unsafe {
-                tcsetattr(fd, TCSANOW, term_orig);
-            }
+            let _ = guard.restore();
         }
-        if let Some(fd) = self.output_fd
-            && let Some(ref mut term_orig) = self.output_term_orig
+    }
+
+    /// Configures the terminals again once they were restored
+    fn reapply_terminal_configuration(&self) -> io::Result<()> {
+        for guard in [&self.input_guard, &self.output_guard]
+            .into_iter()
+            .flatten()
         {
-            unsafe {
-                tcsetattr(fd, TCSANOW, term_orig);
-            }
+            guard.reapply()?;
         }
+        Ok(())
+    }
+}
+
+impl CharSource for RawModeInput {
+    fn read_char(&mut self) -> std::io::Result<char> {
+        read_char(&mut self.input)
+    }
+
+    fn poll_char(&mut self, timeout: Duration) -> io::Result<bool> {
+        let Some(fd) = self.input_fd else {
+            return Ok(true);
+        };
+
+        let mut pollfd = libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        poll(std::slice::from_mut(&mut pollfd), Some(timeout))
     }
 }
 
 impl RawPasswordInput for RawModeInput {
-    fn new(config: Config) -> io::Result<impl RawPasswordInput> {
+    fn new(config: Config) -> io::Result<Self> {
+        let fallback = config.terminal_fallback;
+        let open_default = |path: &str, options: &OpenOptions| match open_terminal(
+            path,
+            options,
+            &STANDARD_STREAMS,
+        ) {
+            Ok(file) => Ok(Some(file)),
+            Err(_) if fallback == TerminalFallback::Stdin => Ok(None),
+            Err(err) => Err(err),
+        };
+
         let mut input_fd: Option<RawFd> = None;
         let input: Box<dyn Read> = match config.input {
+            InputTarget::Terminal => {
+                match open_default(DEFAULT_INPUT_PATH, OpenOptions::new().read(true))? {
+                    Some(file) => {
+                        input_fd = Some(file.as_raw_fd());
+                        Box::new(file)
+                    }
+                    None => Box::new(io::stdin()),
+                }
+            }
             InputTarget::FilePath(path) => {
                 let file = OpenOptions::new().read(true).open(path)?;
                 input_fd = Some(file.as_raw_fd());
@@ -79,14 +386,18 @@ impl RawPasswordInput for RawModeInput {
         } else {
             false
         };
-        let input_term_orig = if input_is_tty && let Some(fd) = input_fd {
-            Some(safe_tcgetattr(fd)?)
-        } else {
-            None
-        };
 
         let mut output_fd: Option<RawFd> = None;
         let output: Box<dyn Write> = match config.output {
+            OutputTarget::Terminal => {
+                match open_default(DEFAULT_OUTPUT_PATH, OpenOptions::new().write(true))? {
+                    Some(file) => {
+                        output_fd = Some(file.as_raw_fd());
+                        Box::new(file)
+                    }
+                    None => Box::new(io::stderr()),
+                }
+            }
             OutputTarget::FilePath(path) => {
                 let file = OpenOptions::new().write(true).open(path)?;
                 output_fd = Some(file.as_raw_fd());
@@ -100,21 +411,19 @@ impl RawPasswordInput for RawModeInput {
         } else {
             false
         };
-        let output_term_orig = if output_is_tty && let Some(fd) = output_fd {
-            Some(safe_tcgetattr(fd)?)
-        } else {
-            None
-        };
 
         Ok(RawModeInput {
             input,
             input_fd,
-            input_term_orig,
             input_is_tty,
+            input_guard: None,
             output,
             output_fd,
-            output_term_orig,
             output_is_tty,
+            output_guard: None,
+            signal_watcher: None,
+            restore_on_exit: config.restore_on_exit,
+            restore_guard: None,
         })
     }
 
@@ -123,31 +432,148 @@ impl RawPasswordInput for RawModeInput {
     }
 
     fn apply_terminal_configuration(&mut self) -> io::Result<()> {
+        // Without flow control, Ctrl-S cannot stop our output and freeze the prompt until Ctrl-Q
+        let mode = RawMode::new();
         if self.input_is_tty
             && let Some(fd) = self.input_fd
         {
-            let mut term = safe_tcgetattr(fd)?;
-            term.c_lflag &= !(ECHO | ICANON | ECHONL | ISIG);
-            term.c_cc[VMIN] = 1;
-            term.c_cc[VTIME] = 0;
-            safe_tcsetattr(fd, &mut term)?;
+            // The descriptor stays open as long as `self.input`
+            self.input_guard = Some(mode.apply(unsafe { BorrowedFd::borrow_raw(fd) })?);
         }
-
         if self.output_is_tty
             && let Some(fd) = self.output_fd
         {
-            let mut term = safe_tcgetattr(fd)?;
-            term.c_lflag &= !(ECHO | ICANON | ECHONL | ISIG);
-            term.c_cc[VMIN] = 1;
-            term.c_cc[VTIME] = 0;
-            safe_tcsetattr(fd, &mut term)?;
+            self.output_guard = Some(mode.apply(unsafe { BorrowedFd::borrow_raw(fd) })?);
+            // Resume output in case Ctrl-S was pressed before the prompt showed up
+            io_result(unsafe { libc::tcflow(fd, TCOON) })?;
+        }
+
+        if self.input_is_tty || self.output_is_tty {
+            self.signal_watcher = Some(SignalWatcher::install()?);
         }
 
+        if self.restore_on_exit {
+            // Restored in this order, see `restore_terminal_configuration`
+            let terminals: Vec<_> = [&self.output_guard, &self.input_guard]
+                .into_iter()
+                .flatten()
+                .map(|guard| (guard.raw_fd(), guard.original(), guard.configured()))
+                .collect();
+            self.restore_guard = RestoreGuard::arm(&terminals)?;
+        }
         Ok(())
     }
 
-    fn read_char(&mut self) -> std::io::Result<char> {
-        read_char(&mut self.input)
+    fn supports_styling(&self) -> bool {
+        self.output_is_tty
+    }
+
+    fn capabilities(&self, terminal: Option<&str>) -> Capabilities {
+        if !self.output_is_tty {
+            return Capabilities::ansi();
+        }
+        match terminal {
+            Some(name) => Capabilities::for_terminal(Some(name)),
+            None => Capabilities::for_terminal(std::env::var("TERM").ok().as_deref()),
+        }
+    }
+
+    fn terminal_columns(&self) -> Option<usize> {
+        if !self.output_is_tty {
+            return None;
+        }
+
+        let fd = self.output_fd?;
+        let mut size = mem::MaybeUninit::<libc::winsize>::uninit();
+        io_result(unsafe { libc::ioctl(fd, libc::TIOCGWINSZ, size.as_mut_ptr()) }).ok()?;
+        let size = unsafe { size.assume_init() };
+        Some(size.ws_col as usize).filter(|&columns| columns > 0)
+    }
+
+    fn wait_for_key(&mut self, timeout: Option<Duration>) -> io::Result<bool> {
+        let Some(fd) = self.input_fd else {
+            return Ok(true);
+        };
+
+        let mut fds = vec![libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        }];
+        if let Some(ref watcher) = self.signal_watcher {
+            fds.push(libc::pollfd {
+                fd: watcher.read_fd,
+                events: libc::POLLIN,
+                revents: 0,
+            });
+        }
+        Ok(poll(&mut fds, timeout)? && fds[0].revents != 0)
+    }
+
+    fn discard_typeahead(&mut self) -> io::Result<bool> {
+        let Some(fd) = self.input_fd else {
+            return Ok(false);
+        };
+
+        let mut pending: c_int = 0;
+        io_result(unsafe { libc::ioctl(fd, libc::FIONREAD, &mut pending) })?;
+        io_result(unsafe { libc::tcflush(fd, libc::TCIFLUSH) })?;
+        Ok(pending > 0)
+    }
+
+    fn resized(&mut self) -> bool {
+        self.signal_watcher
+            .as_ref()
+            .is_some_and(|watcher| watcher.take(&RESIZED))
+    }
+
+    fn stop_requested(&mut self) -> bool {
+        self.signal_watcher
+            .as_ref()
+            .is_some_and(|watcher| watcher.take(&STOP_REQUESTED))
+    }
+
+    fn stop(&mut self) -> io::Result<()> {
+        if self.signal_watcher.is_none() {
+            return Ok(());
+        }
+        // The shell gets the terminal back as it left it
+        self.restore_terminal_configuration();
+        let result = self
+            .signal_watcher
+            .as_ref()
+            .map_or(Ok(()), SignalWatcher::stop);
+        self.reapply_terminal_configuration()?;
+        result
+    }
+
+    fn continued(&mut self) -> io::Result<bool> {
+        let continued = self
+            .signal_watcher
+            .as_ref()
+            .is_some_and(|watcher| watcher.take(&CONTINUED));
+        if continued {
+            // The terminal may have been configured for something else meanwhile
+            self.reapply_terminal_configuration()?;
+        }
+        Ok(continued)
+    }
+
+    fn terminal_bindings(&self) -> Vec<(Action, Key, Option<Key>)> {
+        let Some(term) = self.input_guard.as_ref().map(RawModeGuard::original) else {
+            return Vec::new();
+        };
+
+        SPECIAL_CHARS
+            .iter()
+            .filter(|&&(index, _, usual)| term.c_cc[index] != usual)
+            .map(|&(index, action, usual)| {
+                let c = term.c_cc[index];
+                // Disabled special characters are 0 on Linux and 0xFF on BSDs and macOS
+                let key = (c != 0 && c != 0xFF).then(|| Key::from_char(c as char));
+                (action, Key::from_char(usual as char), key)
+            })
+            .collect()
     }
 
     fn write_output(&mut self, output: &str) -> std::io::Result<()> {
@@ -155,6 +581,18 @@ impl RawPasswordInput for RawModeInput {
         self.output.flush()
     }
 
+    fn send_signal_sigtstp(&mut self) -> io::Result<()> {
+        // Without the watcher, the program would stop with the terminal still configured
+        if self.signal_watcher.is_none() {
+            return Ok(());
+        }
+        // Like the terminal, stop the whole foreground process group
+        if unsafe { libc::kill(0, libc::SIGTSTP) != 0 } {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
     fn send_signal_sigint(&mut self) -> io::Result<()> {
         if unsafe { libc::raise(libc::SIGINT) != 0 } {
             return Err(std::io::Error::last_os_error());
@@ -165,8 +603,99 @@ impl RawPasswordInput for RawModeInput {
 
 #[cfg(test)]
 mod tests {
-    use crate::config::ConfigBuilder;
+    use crate::config::{ConfigBuilder, Typeahead};
+    use crate::raw_mode::{safe_tcgetattr, safe_tcsetattr};
     use crate::read_password_with_config;
+    use libc::{ECHO, ICANON, O_NOCTTY, O_RDWR, OPOST, VINTR, VKILL, VWERASE, c_int};
+    use std::ffi::CStr;
+    use std::fs::File;
+    use std::io::{Read, Write};
+    use std::os::fd::FromRawFd;
+
+    /// Opens a pseudo-terminal, returning its master side and the path to its slave side
+    fn open_pty() -> (File, String) {
+        unsafe {
+            let master = libc::posix_openpt(O_RDWR | O_NOCTTY | libc::O_CLOEXEC);
+            assert!(master >= 0);
+            assert_eq!(libc::grantpt(master), 0);
+            assert_eq!(libc::unlockpt(master), 0);
+            let path = CStr::from_ptr(libc::ptsname(master))
+                .to_str()
+                .unwrap()
+                .to_string();
+            (File::from_raw_fd(master), path)
+        }
+    }
+
+    /// Runs `test` again in a process of its own, with `variable` set to `value` so that it takes
+    /// the child's part, for tests that stop the process or change its signal handlers
+    fn spawn_test_child(test: &str, variable: &str, value: &str) -> std::process::Child {
+        use std::os::unix::process::CommandExt;
+
+        std::process::Command::new(std::env::current_exe().unwrap())
+            .args(["--exact", test, "--nocapture", "--test-threads=1"])
+            .env(variable, value)
+            .stdout(std::process::Stdio::piped())
+            // A group of its own is not orphaned, so the child can be stopped
+            .process_group(0)
+            .spawn()
+            .unwrap()
+    }
+
+    /// Changes the termios of the pseudo-terminal at `path`, like `stty` would
+    fn stty(path: &str, configure: impl FnOnce(&mut libc::termios)) -> File {
+        let slave = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .unwrap();
+        let fd: c_int = std::os::fd::AsRawFd::as_raw_fd(&slave);
+        let mut term = safe_tcgetattr(fd).unwrap();
+        configure(&mut term);
+        safe_tcsetattr(fd, &term).unwrap();
+        slave
+    }
+
+    #[test]
+    fn test_read_password_with_config_honors_stty_special_chars() {
+        let (mut master, path) = open_pty();
+        // Keep the slave open so the settings stick, and disable the kernel line editing so
+        // that only our code interprets the special chars
+        let _slave = stty(&path, |term| {
+            term.c_lflag &= !ICANON;
+            term.c_cc[VKILL] = 0x18;
+            term.c_cc[VWERASE] = 0x14;
+        });
+        master.write_all(b"nope\x18my pass\x14word\n").unwrap();
+
+        let config = ConfigBuilder::new()
+            .input_file_path(path)
+            .output_discard()
+            .build();
+
+        let result = read_password_with_config(config);
+        assert_eq!("my word", result.unwrap());
+    }
+
+    #[test]
+    fn stty_special_chars_override_the_keymap() {
+        let (mut master, path) = open_pty();
+        let _slave = stty(&path, |term| {
+            term.c_lflag &= !ICANON;
+            term.c_cc[VWERASE] = 0x01;
+            term.c_cc[VINTR] = 0;
+        });
+        // Ctrl-A erases a word instead of moving home, Ctrl-C no longer cancels
+        master.write_all(b"my pass\x01wo\x03rd\n").unwrap();
+
+        let config = ConfigBuilder::new()
+            .input_file_path(path)
+            .output_discard()
+            .build();
+
+        let result = read_password_with_config(config);
+        assert_eq!("my word", result.unwrap());
+    }
 
     #[test]
     fn test_read_password_with_config_errors_with_file_not_found() {
@@ -182,4 +711,374 @@ mod tests {
         let err = result.unwrap_err();
         assert_eq!(err.raw_os_error(), Some(libc::ENOENT));
     }
+
+    #[test]
+    fn test_read_password_with_config_recognizes_bare_esc() {
+        let (mut master, path) = open_pty();
+        let _slave = stty(&path, |term| term.c_lflag &= !ICANON);
+        master.write_all(b"pass\x1B").unwrap();
+        let typing = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(200));
+            master.write_all(b"word\n").unwrap();
+            master
+        });
+
+        let config = ConfigBuilder::new()
+            .input_file_path(path)
+            .output_discard()
+            .build();
+
+        let result = read_password_with_config(config);
+        assert_eq!("password", result.unwrap());
+        typing.join().unwrap();
+    }
+
+    #[test]
+    fn test_read_password_with_config_toggles_bracketed_paste() {
+        let (mut master, path) = open_pty();
+        // Without echo, the output is only what we write
+        let _slave = stty(&path, |term| term.c_lflag &= !(ICANON | ECHO));
+        master.write_all(b"\x1B[200~password\n\x1B[201~\n").unwrap();
+
+        let config = ConfigBuilder::new()
+            .input_file_path(path.as_str())
+            .output_file_path(path.as_str())
+            .terminal("xterm")
+            .build();
+
+        let entry = crate::read_password_entry_with_config(config).unwrap();
+        assert_eq!("password", entry.password);
+        assert!(entry.pasted);
+
+        let mut output = Vec::new();
+        while !output.ends_with(b"\x1B[?2004l") {
+            let mut buf = [0; 64];
+            let n = master.read(&mut buf).unwrap();
+            output.extend_from_slice(&buf[..n]);
+        }
+        assert!(output.starts_with(b"\x1B[?2004h"));
+    }
+
+    #[test]
+    fn test_read_password_with_config_redraws_from_line_start_on_dumb_terminals() {
+        let (mut master, path) = open_pty();
+        let _slave = stty(&path, |term| term.c_lflag &= !(ICANON | ECHO));
+        master.write_all(b"ab\x7F\n").unwrap();
+
+        let config = ConfigBuilder::new()
+            .input_file_path(path.as_str())
+            .output_file_path(path.as_str())
+            .terminal("dumb")
+            .password_feedback_mask('*')
+            .build();
+
+        let entry = crate::prompt_password_entry_with_config("> ", config).unwrap();
+        assert_eq!("a", entry.password);
+
+        let mut output = Vec::new();
+        while !output.ends_with(b"\n") {
+            let mut buf = [0; 64];
+            let n = master.read(&mut buf).unwrap();
+            output.extend_from_slice(&buf[..n]);
+        }
+        // Without cursor_left, the line is drawn again after going back to its start, and there
+        // is no bracketed paste
+        assert_eq!(
+            String::from_utf8_lossy(&output),
+            "> \r> *\r> **\r> * \r> *\r\n"
+        );
+    }
+
+    #[test]
+    fn test_read_password_with_config_discards_typeahead() {
+        let (mut master, path) = open_pty();
+        let slave = stty(&path, |term| term.c_lflag &= !(ICANON | ECHO));
+        master.write_all(b"injected\n").unwrap();
+        // The typeahead reaches the slave side asynchronously
+        let mut pending: c_int = 0;
+        while pending == 0 {
+            let fd = std::os::fd::AsRawFd::as_raw_fd(&slave);
+            assert_eq!(unsafe { libc::ioctl(fd, libc::FIONREAD, &mut pending) }, 0);
+        }
+        let typing = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(300));
+            master.write_all(b"password\n").unwrap();
+            master
+        });
+
+        let config = ConfigBuilder::new()
+            .input_file_path(path.as_str())
+            .output_file_path(path.as_str())
+            .terminal("xterm")
+            .typeahead(Typeahead::Warn)
+            .build();
+
+        let result = read_password_with_config(config);
+        assert_eq!("password", result.unwrap());
+
+        let mut master = typing.join().unwrap();
+        let mut output = Vec::new();
+        while !output.ends_with(b"\x1B[?2004l") {
+            let mut buf = [0; 64];
+            let n = master.read(&mut buf).unwrap();
+            output.extend_from_slice(&buf[..n]);
+        }
+        assert!(output.starts_with(b"Input typed before the prompt was discarded\r\n"));
+    }
+
+    #[test]
+    fn test_read_password_with_config_masks_revealed_password_again() {
+        let (mut master, path) = open_pty();
+        let _slave = stty(&path, |term| term.c_lflag &= !(ICANON | ECHO));
+        master.write_all(b"ab\x12").unwrap();
+        let typing = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(300));
+            master.write_all(b"\n").unwrap();
+            master
+        });
+
+        let config = ConfigBuilder::new()
+            .input_file_path(path.as_str())
+            .output_file_path(path.as_str())
+            .terminal("xterm")
+            .password_feedback_mask('*')
+            .allow_reveal()
+            .reveal_timeout(std::time::Duration::from_millis(50))
+            .build();
+
+        let result = read_password_with_config(config);
+        assert_eq!("ab", result.unwrap());
+
+        let mut master = typing.join().unwrap();
+        let mut output = Vec::new();
+        while !output.ends_with(b"\x1B[?2004l") {
+            let mut buf = [0; 64];
+            let n = master.read(&mut buf).unwrap();
+            output.extend_from_slice(&buf[..n]);
+        }
+        assert_eq!(output, b"\x1B[?2004h**\x08\x08ab\x08\x08**\r\n\x1B[?2004l");
+    }
+
+    #[test]
+    fn test_prompt_password_with_config_clears_screen() {
+        let (mut master, path) = open_pty();
+        let _slave = stty(&path, |term| term.c_lflag &= !(ICANON | ECHO));
+        master.write_all(b"ab\x0C\n").unwrap();
+
+        let config = ConfigBuilder::new()
+            .input_file_path(path.as_str())
+            .output_file_path(path.as_str())
+            .terminal("xterm")
+            .password_feedback_mask('*')
+            .build();
+
+        let result = crate::prompt_password_with_config("Password: ", config);
+        assert_eq!("ab", result.unwrap());
+
+        let mut output = Vec::new();
+        while !output.ends_with(b"\x1B[?2004l") {
+            let mut buf = [0; 64];
+            let n = master.read(&mut buf).unwrap();
+            output.extend_from_slice(&buf[..n]);
+        }
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "Password: \x1B[?2004h**\x1B[H\x1B[2JPassword: **\r\n\x1B[?2004l"
+        );
+    }
+
+    #[test]
+    fn test_prompt_password_with_config_erases_across_wrapped_rows() {
+        let (mut master, path) = open_pty();
+        // Without output processing, newlines are written as is
+        let _slave = stty(&path, |term| {
+            term.c_lflag &= !(ICANON | ECHO);
+            term.c_oflag &= !OPOST;
+        });
+        let size = libc::winsize {
+            ws_row: 24,
+            ws_col: 4,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        let fd = std::os::fd::AsRawFd::as_raw_fd(&master);
+        assert_eq!(unsafe { libc::ioctl(fd, libc::TIOCSWINSZ, &size) }, 0);
+        master.write_all(b"ab\x7F\n").unwrap();
+
+        let config = ConfigBuilder::new()
+            .input_file_path(path.as_str())
+            .output_file_path(path.as_str())
+            .terminal("xterm")
+            .password_feedback_mask('*')
+            .build();
+
+        let result = crate::prompt_password_with_config("> ", config);
+        assert_eq!("a", result.unwrap());
+
+        let mut output = Vec::new();
+        while !output.ends_with(b"\x1B[?2004l") {
+            let mut buf = [0; 64];
+            let n = master.read(&mut buf).unwrap();
+            output.extend_from_slice(&buf[..n]);
+        }
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "> \x1B[?2004h**\r\n\x1B[1A\x1B[4G \r\n\x1B[1A\x1B[4G\n\x1B[?2004l"
+        );
+    }
+
+    #[test]
+    fn open_terminal_tries_standard_streams() {
+        use std::os::fd::AsRawFd;
+
+        let (_master, path) = open_pty();
+        let slave = stty(&path, |_| {});
+        let null = File::open("/dev/null").unwrap();
+        let mut options = std::fs::OpenOptions::new();
+        options.read(true);
+
+        let streams = [(null.as_raw_fd(), "stdin"), (slave.as_raw_fd(), "stderr")];
+        let file = super::open_terminal("/nonexistent/tty", &options, &streams).unwrap();
+        assert_eq!(
+            super::ttyname(file.as_raw_fd()).unwrap().to_str(),
+            Some(&*path)
+        );
+
+        let err = super::open_terminal("/nonexistent/tty", &options, &streams[..1]).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+        assert_eq!(
+            err.to_string(),
+            "no terminal to read the password from (/nonexistent/tty: No such file or directory \
+             (os error 2), stdin: not a terminal)"
+        );
+    }
+
+    #[test]
+    fn key_reader_stops_and_continues() {
+        use crate::key::{Key, KeyReader};
+        use std::io::{BufRead, BufReader};
+        use std::time::{Duration, Instant};
+
+        const CHILD_TERMINAL: &str = "RPASSWORD_TEST_KEY_READER_TERMINAL";
+        // The child holds the reader, since stopping it stops the whole process
+        if let Ok(path) = std::env::var(CHILD_TERMINAL) {
+            let config = ConfigBuilder::new()
+                .input_file_path(&path)
+                .output_file_path(&path)
+                .build();
+            let mut reader = KeyReader::from_config(config).unwrap();
+            println!("ready");
+            println!("key: {:?}", reader.read_key().unwrap());
+            return;
+        }
+
+        let (mut master, path) = open_pty();
+        let slave = stty(&path, |_| {});
+        let fd = std::os::fd::AsRawFd::as_raw_fd(&slave);
+        let echo = || safe_tcgetattr(fd).unwrap().c_lflag & ECHO != 0;
+        let mut child = spawn_test_child(
+            "unix::tests::key_reader_stops_and_continues",
+            CHILD_TERMINAL,
+            &path,
+        );
+        let pid = child.id() as libc::pid_t;
+        let mut lines = BufReader::new(child.stdout.take().unwrap()).lines();
+        // The test harness prints the name of the test on the same line
+        while !lines.next().unwrap().unwrap().ends_with("ready") {}
+        assert!(!echo());
+
+        unsafe {
+            libc::kill(pid, libc::SIGTSTP);
+        }
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut status = 0;
+        while unsafe { libc::waitpid(pid, &mut status, libc::WUNTRACED | libc::WNOHANG) } == 0 {
+            assert!(Instant::now() < deadline, "the child did not stop");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(libc::WIFSTOPPED(status));
+        assert!(echo());
+
+        unsafe {
+            libc::kill(pid, libc::SIGCONT);
+        }
+        while echo() {
+            assert!(
+                Instant::now() < deadline,
+                "the terminal was not configured again"
+            );
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        master.write_all(b"a").unwrap();
+        let key = format!("key: {:?}", Some(Key::Char('a')));
+        while !lines.next().unwrap().unwrap().ends_with(&key) {}
+        assert!(child.wait().unwrap().success());
+    }
+
+    #[test]
+    fn signal_watcher_chains_previous_handlers() {
+        use super::{CONTINUED, RESIZED, STOP_REQUESTED, SignalWatcher};
+        use std::io::Read as _;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static RECEIVED: [AtomicUsize; 2] = [AtomicUsize::new(0), AtomicUsize::new(0)];
+        extern "C" fn count(signal: c_int) {
+            let index = usize::from(signal == libc::SIGTSTP);
+            RECEIVED[index].fetch_add(1, Ordering::Relaxed);
+        }
+        let action = |signal: c_int| {
+            let mut action: libc::sigaction = unsafe { std::mem::zeroed() };
+            unsafe { libc::sigaction(signal, std::ptr::null(), &mut action) };
+            action.sa_sigaction
+        };
+
+        const CHILD: &str = "RPASSWORD_TEST_SIGNAL_WATCHER";
+        // Signal handlers are shared by the whole process, so the child runs on its own
+        if std::env::var(CHILD).is_ok() {
+            let mut handler: libc::sigaction = unsafe { std::mem::zeroed() };
+            handler.sa_sigaction = count as extern "C" fn(c_int) as libc::sighandler_t;
+            for signal in [libc::SIGWINCH, libc::SIGTSTP] {
+                assert_eq!(
+                    unsafe { libc::sigaction(signal, &handler, std::ptr::null_mut()) },
+                    0
+                );
+            }
+
+            let watcher = SignalWatcher::install().unwrap();
+            unsafe { libc::raise(libc::SIGWINCH) };
+            assert!(watcher.take(&RESIZED));
+            assert_eq!(RECEIVED[0].load(Ordering::Relaxed), 1);
+
+            // The program's handler runs once the read loop stops, instead of stopping
+            unsafe { libc::raise(libc::SIGTSTP) };
+            assert!(watcher.take(&STOP_REQUESTED));
+            assert_eq!(RECEIVED[1].load(Ordering::Relaxed), 0);
+            watcher.stop().unwrap();
+            assert_eq!(RECEIVED[1].load(Ordering::Relaxed), 1);
+            assert!(!watcher.take(&CONTINUED));
+            assert_ne!(action(libc::SIGTSTP), handler.sa_sigaction);
+
+            drop(watcher);
+            assert_eq!(action(libc::SIGWINCH), handler.sa_sigaction);
+            assert_eq!(action(libc::SIGTSTP), handler.sa_sigaction);
+            println!("passed");
+            return;
+        }
+
+        let mut child = spawn_test_child(
+            "unix::tests::signal_watcher_chains_previous_handlers",
+            CHILD,
+            "1",
+        );
+        let mut output = String::new();
+        child
+            .stdout
+            .take()
+            .unwrap()
+            .read_to_string(&mut output)
+            .unwrap();
+        assert!(child.wait().unwrap().success(), "{output}");
+        // The test harness prints the name of the test on the same line
+        assert!(output.contains("passed"), "{output}");
+    }
 }
@@ -1,19 +1,54 @@
 use crate::RawPasswordInput;
-use crate::config::{Config, InputTarget};
+use crate::config::{Config, InputTarget, OutputTarget, TerminalFallback};
+use crate::key::CharSource;
+use crate::utf8::read_char;
 use rtoolbox::fix_line_issues::fix_line_issues;
 use std::fs::OpenOptions;
-use std::io::{self, BufRead, BufReader, Read};
+use std::io::{self, BufRead, BufReader, Cursor, Read, Write};
 
 pub(crate) const DEFAULT_INPUT_PATH: &str = "/dev/stdin";
 pub(crate) const DEFAULT_OUTPUT_PATH: &str = "/dev/stdout";
 
 pub(crate) struct RawModeInput {
-    config: Config,
+    input: Box<dyn Read>,
+    output: Box<dyn Write>,
+}
+
+impl CharSource for RawModeInput {
+    fn read_char(&mut self) -> std::io::Result<char> {
+        read_char(&mut self.input)
+    }
+
+    fn poll_char(&mut self, _timeout: std::time::Duration) -> std::io::Result<bool> {
+        Ok(true)
+    }
 }
 
 impl RawPasswordInput for RawModeInput {
-    fn new(config: Config) -> io::Result<impl RawPasswordInput> {
-        Ok(RawModeInput { config })
+    fn new(config: Config) -> io::Result<Self> {
+        let fallback = config.terminal_fallback;
+        let input: Box<dyn Read> = match config.input {
+            InputTarget::Terminal => match OpenOptions::new().read(true).open(DEFAULT_INPUT_PATH) {
+                Ok(file) => Box::new(file),
+                Err(_) if fallback == TerminalFallback::Stdin => Box::new(io::stdin()),
+                Err(err) => return Err(err),
+            },
+            InputTarget::FilePath(path) => Box::new(OpenOptions::new().read(true).open(path)?),
+            InputTarget::Reader(reader) => reader,
+        };
+        let output: Box<dyn Write> = match config.output {
+            OutputTarget::Terminal => {
+                match OpenOptions::new().write(true).open(DEFAULT_OUTPUT_PATH) {
+                    Ok(file) => Box::new(file),
+                    Err(_) if fallback == TerminalFallback::Stdin => Box::new(io::stderr()),
+                    Err(err) => return Err(err),
+                }
+            }
+            OutputTarget::FilePath(path) => Box::new(OpenOptions::new().write(true).open(path)?),
+            OutputTarget::Writer(writer) => Box::new(writer),
+            OutputTarget::Void => Box::new(Cursor::new(Vec::<u8>::new())),
+        };
+        Ok(RawModeInput { input, output })
     }
 
     fn needs_terminal_configuration(&self) -> bool {
@@ -24,27 +59,23 @@ impl RawPasswordInput for RawModeInput {
         Ok(())
     }
 
-    #[allow(unused)]
-    fn read_char(&mut self) -> std::io::Result<char> {
-        unimplemented!()
-    }
-
     fn read_password(
         &mut self,
-        _password_feedback: crate::PasswordFeedback,
-    ) -> std::io::Result<String> {
-        let input: Box<dyn Read> = match &mut self.config.input {
-            InputTarget::FilePath(path) => Box::new(OpenOptions::new().read(true).open(path)?),
-            InputTarget::Reader(reader) => Box::new(reader),
-        };
-        let mut reader = BufReader::new(input);
+        options: crate::config::ReadOptions,
+    ) -> std::io::Result<crate::PasswordEntry> {
+        self.write_output(&options.prompt)?;
+        let mut reader = BufReader::new(&mut self.input);
         let mut line = String::new();
         reader.read_line(&mut line)?;
-        fix_line_issues(line)
+        Ok(crate::PasswordEntry {
+            password: fix_line_issues(line)?,
+            pasted: false,
+        })
     }
 
-    fn write_output(&mut self, _output: &str) -> std::io::Result<()> {
-        Ok(())
+    fn write_output(&mut self, output: &str) -> std::io::Result<()> {
+        self.output.write_all(output.as_bytes())?;
+        self.output.flush()
     }
 
     fn send_signal_sigint(&mut self) -> io::Result<()> {
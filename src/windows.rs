@@ -1,20 +1,35 @@
 use crate::RawPasswordInput;
-use crate::config::{Config, InputTarget, OutputTarget};
+use crate::config::{Config, InputTarget, OutputTarget, TerminalFallback};
+use crate::key::CharSource;
+use crate::terminfo::Capabilities;
 use crate::utf8::read_char;
 use std::io;
 use std::io::{Cursor, Read, Write};
-use windows_sys::Win32::Foundation::{GENERIC_READ, GENERIC_WRITE, HANDLE, INVALID_HANDLE_VALUE};
+use std::time::{Duration, Instant};
+use windows_sys::Win32::Foundation::{
+    DUPLICATE_SAME_ACCESS, DuplicateHandle, GENERIC_READ, GENERIC_WRITE, HANDLE,
+    INVALID_HANDLE_VALUE, WAIT_OBJECT_0, WAIT_TIMEOUT,
+};
 use windows_sys::Win32::Storage::FileSystem::{
     CreateFileW, FILE_SHARE_READ, FILE_SHARE_WRITE, OPEN_EXISTING, ReadFile, WriteFile,
 };
 use windows_sys::Win32::System::Console::{
-    CONSOLE_MODE, CTRL_C_EVENT, ENABLE_PROCESSED_INPUT, GenerateConsoleCtrlEvent, GetConsoleMode,
-    ReadConsoleW, SetConsoleMode, WriteConsoleW,
+    CONSOLE_MODE, CTRL_C_EVENT, GenerateConsoleCtrlEvent, GetConsoleMode,
+    GetNumberOfConsoleInputEvents, GetStdHandle, INPUT_RECORD, KEY_EVENT, PeekConsoleInputW,
+    ReadConsoleInputW, ReadConsoleW, STD_ERROR_HANDLE, STD_HANDLE, STD_INPUT_HANDLE,
+    STD_OUTPUT_HANDLE, SetConsoleMode, WriteConsoleW,
 };
+use windows_sys::Win32::System::Threading::{GetCurrentProcess, INFINITE, WaitForSingleObject};
 
 pub(crate) const DEFAULT_INPUT_PATH: &str = "CONIN$";
 pub(crate) const DEFAULT_OUTPUT_PATH: &str = "CONOUT$";
 
+/// Standard streams whose console is read when `CONIN$` cannot be opened
+const INPUT_STREAMS: [(STD_HANDLE, &str); 1] = [(STD_INPUT_HANDLE, "stdin")];
+/// Standard streams whose console is written to when `CONOUT$` cannot be opened
+const OUTPUT_STREAMS: [(STD_HANDLE, &str); 2] =
+    [(STD_ERROR_HANDLE, "stderr"), (STD_OUTPUT_HANDLE, "stdout")];
+
 fn is_interactive_terminal(handle: windows_sys::Win32::Foundation::HANDLE) -> bool {
     let mut mode: CONSOLE_MODE = 0;
     unsafe {
@@ -53,6 +68,64 @@ fn open_file_or_console(path: &str) -> io::Result<HANDLE> {
     Ok(handle)
 }
 
+/// Returns a handle of its own to the console `handle` refers to, to be closed like any other
+fn duplicate_handle(handle: HANDLE) -> io::Result<HANDLE> {
+    let mut duplicate = INVALID_HANDLE_VALUE;
+    let process = unsafe { GetCurrentProcess() };
+    if unsafe {
+        DuplicateHandle(
+            process,
+            handle,
+            process,
+            &mut duplicate,
+            0,
+            0,
+            DUPLICATE_SAME_ACCESS,
+        )
+    } == 0
+    {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    Ok(duplicate)
+}
+
+/// Opens the console at `path`, or else the console of the first of `streams` that is connected
+/// to one, returning `None` if there is none and the fallback is to be used. Fails with every
+/// attempt in the error message.
+fn open_console(
+    path: &str,
+    streams: &[(STD_HANDLE, &str)],
+    fallback: TerminalFallback,
+) -> io::Result<Option<HANDLE>> {
+    let mut tried = Vec::new();
+    match open_file_or_console(path) {
+        Ok(handle) => return Ok(Some(handle)),
+        Err(err) => tried.push(format!("{path}: {err}")),
+    }
+    for &(stream, name) in streams {
+        let handle = unsafe { GetStdHandle(stream) };
+        if !is_interactive_terminal(handle) {
+            tried.push(format!("{name}: not a console"));
+            continue;
+        }
+        match duplicate_handle(handle) {
+            Ok(handle) => return Ok(Some(handle)),
+            Err(err) => tried.push(format!("{name}: {err}")),
+        }
+    }
+    if fallback == TerminalFallback::Stdin {
+        return Ok(None);
+    }
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        format!(
+            "no console to read the password from ({})",
+            tried.join(", ")
+        ),
+    ))
+}
+
 fn read_wchar_from_console(handle: windows_sys::Win32::Foundation::HANDLE) -> io::Result<u16> {
     let mut buf: [u16; 1] = [0];
     let mut wchars_read: u32 = 0;
@@ -112,6 +185,70 @@ fn read_char_from_console(handle: windows_sys::Win32::Foundation::HANDLE) -> io:
     }
 }
 
+/// Waits until `ReadConsoleW` has a char to return, discarding the mouse, focus and key release
+/// events it would skip anyway
+fn wait_for_console_key(handle: HANDLE, timeout: Duration) -> io::Result<bool> {
+    let deadline = Instant::now().checked_add(timeout);
+    loop {
+        let millis = match deadline {
+            Some(deadline) => {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                remaining.as_millis().min(INFINITE as u128 - 1) as u32
+            }
+            None => INFINITE,
+        };
+        match unsafe { WaitForSingleObject(handle, millis) } {
+            WAIT_OBJECT_0 => {}
+            WAIT_TIMEOUT => return Ok(false),
+            _ => return Err(io::Error::last_os_error()),
+        }
+
+        let mut record: INPUT_RECORD = unsafe { std::mem::zeroed() };
+        let mut count: u32 = 0;
+        if unsafe { PeekConsoleInputW(handle, &mut record, 1, &mut count) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if count == 0 {
+            continue;
+        }
+        if record.EventType as u32 == KEY_EVENT {
+            let key_event = unsafe { record.Event.KeyEvent };
+            if key_event.bKeyDown != 0 && unsafe { key_event.uChar.UnicodeChar } != 0 {
+                return Ok(true);
+            }
+        }
+        if unsafe { ReadConsoleInputW(handle, &mut record, 1, &mut count) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+}
+
+/// Reads and drops the pending console input events, returning whether any was a typed char
+fn discard_console_input(handle: HANDLE) -> io::Result<bool> {
+    let mut discarded = false;
+    loop {
+        let mut events: u32 = 0;
+        if unsafe { GetNumberOfConsoleInputEvents(handle, &mut events) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if events == 0 {
+            return Ok(discarded);
+        }
+
+        let mut record: INPUT_RECORD = unsafe { std::mem::zeroed() };
+        let mut count: u32 = 0;
+        if unsafe { ReadConsoleInputW(handle, &mut record, 1, &mut count) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if record.EventType as u32 == KEY_EVENT {
+            let key_event = unsafe { record.Event.KeyEvent };
+            if key_event.bKeyDown != 0 && unsafe { key_event.uChar.UnicodeChar } != 0 {
+                discarded = true;
+            }
+        }
+    }
+}
+
 fn read_byte_from_file(handle: windows_sys::Win32::Foundation::HANDLE) -> io::Result<u8> {
     let mut buf_bytes: [u8; 1] = [0];
     let mut bytes_read: u32 = 0;
@@ -310,25 +447,58 @@ impl Drop for RawModeInput {
     }
 }
 
+impl CharSource for RawModeInput {
+    fn read_char(&mut self) -> io::Result<char> {
+        match self.input {
+            WindowsInput::Console(handle) => read_char_from_console(handle),
+            WindowsInput::File(handle) => read_char_from_file(handle),
+            WindowsInput::Reader(ref mut reader) => read_char(reader),
+        }
+    }
+
+    fn poll_char(&mut self, _timeout: Duration) -> io::Result<bool> {
+        // Without ENABLE_VIRTUAL_TERMINAL_INPUT, the console never sends escape sequences, so
+        // Esc is always pressed on its own
+        Ok(!self.input.is_console())
+    }
+}
+
 impl RawPasswordInput for RawModeInput {
-    fn new(config: Config) -> io::Result<impl RawPasswordInput> {
+    fn new(config: Config) -> io::Result<Self> {
+        let input_from_handle = |input_handle| {
+            if is_interactive_terminal(input_handle) {
+                WindowsInput::Console(input_handle)
+            } else {
+                WindowsInput::File(input_handle)
+            }
+        };
         let input = match config.input {
-            InputTarget::FilePath(path) => {
-                let input_handle = open_file_or_console(path.as_str())?;
-                let is_console = is_interactive_terminal(input_handle);
-
-                if is_console {
-                    WindowsInput::Console(input_handle)
-                } else {
-                    WindowsInput::File(input_handle)
+            InputTarget::Terminal => {
+                match open_console(DEFAULT_INPUT_PATH, &INPUT_STREAMS, config.terminal_fallback)? {
+                    Some(input_handle) => input_from_handle(input_handle),
+                    None => WindowsInput::Reader(Box::new(io::stdin())),
                 }
             }
+            InputTarget::FilePath(path) => input_from_handle(open_file_or_console(path.as_str())?),
             InputTarget::Reader(reader) => WindowsInput::Reader(reader),
         };
 
         let input_handle = input.handle();
 
         let output = match config.output {
+            OutputTarget::Terminal => {
+                match open_console(
+                    DEFAULT_OUTPUT_PATH,
+                    &OUTPUT_STREAMS,
+                    config.terminal_fallback,
+                )? {
+                    Some(output_handle) if is_interactive_terminal(output_handle) => {
+                        WindowsOutput::Console(output_handle)
+                    }
+                    Some(output_handle) => WindowsOutput::File(output_handle),
+                    None => WindowsOutput::Writer(Box::new(io::stderr())),
+                }
+            }
             OutputTarget::FilePath(path) => {
                 let output_handle = open_file_or_console(path.as_str())?;
                 let is_console = is_interactive_terminal(output_handle);
@@ -379,10 +549,37 @@ impl RawPasswordInput for RawModeInput {
         self.input.is_console()
     }
 
+    fn wait_for_key(&mut self, timeout: Option<Duration>) -> io::Result<bool> {
+        // Waiting longer than `Instant` can count is waiting forever
+        let timeout = timeout.unwrap_or(Duration::MAX);
+        match self.input {
+            WindowsInput::Console(handle) => wait_for_console_key(handle, timeout),
+            _ => self.poll_char(timeout),
+        }
+    }
+
+    fn capabilities(&self, _terminal: Option<&str>) -> Capabilities {
+        Capabilities::console()
+    }
+
+    fn discard_typeahead(&mut self) -> io::Result<bool> {
+        match self.input {
+            WindowsInput::Console(handle) => discard_console_input(handle),
+            _ => Ok(false),
+        }
+    }
+
+    fn supports_bracketed_paste(&self) -> bool {
+        // The console is read without virtual terminal input, so pastes arrive as typed keys
+        false
+    }
+
     fn apply_terminal_configuration(&mut self) -> io::Result<()> {
+        // Without ENABLE_PROCESSED_INPUT, Ctrl-C arrives as a key for the keymap to handle, and
+        // only `send_signal_sigint` raises the Ctrl-C event
         if self.input.is_console()
             && let Some(handle) = self.input.handle()
-            && unsafe { SetConsoleMode(handle, ENABLE_PROCESSED_INPUT) } == 0
+            && unsafe { SetConsoleMode(handle, 0) } == 0
         {
             return Err(std::io::Error::last_os_error());
         }
@@ -390,14 +587,6 @@ impl RawPasswordInput for RawModeInput {
         Ok(())
     }
 
-    fn read_char(&mut self) -> io::Result<char> {
-        match self.input {
-            WindowsInput::Console(handle) => read_char_from_console(handle),
-            WindowsInput::File(handle) => read_char_from_file(handle),
-            WindowsInput::Reader(ref mut reader) => read_char(reader),
-        }
-    }
-
     fn write_output(&mut self, output: &str) -> std::io::Result<()> {
         match self.output {
             WindowsOutput::Console(handle) => write_output_to_console(handle, output),
@@ -419,8 +608,10 @@ impl RawPasswordInput for RawModeInput {
 
 #[cfg(test)]
 mod tests {
+    use crate::config::TerminalFallback;
     use crate::{ConfigBuilder, read_password_with_config};
     use windows_sys::Win32::Foundation::ERROR_FILE_NOT_FOUND;
+    use windows_sys::Win32::System::Console::{GetStdHandle, STD_INPUT_HANDLE};
 
     #[test]
     fn test_read_password_with_config_errors_with_file_not_found() {
@@ -436,4 +627,22 @@ mod tests {
         let err = result.unwrap_err();
         assert_eq!(err.raw_os_error(), Some(ERROR_FILE_NOT_FOUND as i32));
     }
+
+    #[test]
+    fn open_console_tells_every_attempt() {
+        if super::is_interactive_terminal(unsafe { GetStdHandle(STD_INPUT_HANDLE) }) {
+            return;
+        }
+
+        let err = super::open_console(
+            "C:\\not-found.txt",
+            &super::INPUT_STREAMS,
+            TerminalFallback::Fail,
+        )
+        .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+        let message = err.to_string();
+        assert!(message.starts_with("no console to read the password from (C:\\not-found.txt: "));
+        assert!(message.ends_with(", stdin: not a console)"));
+    }
 }